@@ -1,31 +1,49 @@
-use crate::aws::fetch_instances;
-use crate::aws::InstanceInfo;
+use crate::aws::{InstanceInfo, InstanceMetrics};
+use crate::aws::{
+    current_profile, fetch_console_output, fetch_console_screenshot, fetch_instance_metrics,
+    fetch_instances, fetch_profile_instances, parse_filter, profile_override, set_profile,
+    split_filters, FIELDS,
+};
+use crate::cache::{load_inventory, save_inventory};
+use crate::components::bookmark_list::BookmarkList;
+use crate::components::column_chooser::ColumnChooser;
+use crate::components::console_output::ConsoleOutput;
+use crate::components::error_panel::ErrorPanel;
 use crate::components::instance_details::InstanceDetails;
 use crate::components::instance_table::InstanceTable;
+use crate::components::recent_list::RecentList;
 use crate::components::region_list::RegionList;
 use crate::components::text_input::TextInput;
+use crate::components::theme::get_theme;
 use crate::components::RenderHelp;
 use crate::components::{Action, HandleAction, Render};
+use crate::history::{load_recent, HistoryEntry};
+use crate::sort::{load_sort, save_sort};
+use crate::ui::Tui;
 
 use aws_config::Region;
 use crossterm::event::{self};
 
-use ratatui::style::{Color, Style};
-use ratatui::text::Span;
 use ratatui::{prelude::*, widgets::*};
 
-use std::io::Stdout;
 use std::rc::Rc;
+use std::time::Duration;
 
 use anyhow::Result;
 use thiserror::Error;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
 
-mod config;
+pub mod config;
+use config::{Bookmark, Column};
 
 #[derive(Debug, Clone)]
 pub enum AppStatus {
+    RecentState,
+    BookmarksState,
     RegionSelectState,
     MainScreen,
+    ConsoleOutputState,
+    ErrorState,
 }
 
 #[derive(Error, Debug)]
@@ -34,7 +52,24 @@ pub enum RuntimeError {
     UserExit,
 }
 
-#[derive(Debug, Clone)]
+/// Results of work spawned in the background, so that slow AWS calls don't block the UI
+#[derive(Debug)]
+enum BackgroundEvent {
+    Metrics(String, Result<InstanceMetrics>),
+    Instances {
+        region: String,
+        filters: Vec<String>,
+        instances: Result<Vec<InstanceInfo>>,
+    },
+    /// A past connection looked up again with its profile before reconnecting
+    Revalidated {
+        entry: HistoryEntry,
+        profile: Option<String>,
+        instances: Result<Vec<InstanceInfo>>,
+    },
+}
+
+#[derive(Debug)]
 pub struct App {
     config: config::Config,
     info_panel_enabled: bool,
@@ -44,14 +79,45 @@ pub struct App {
     instances_table_component: InstanceTable,
     search_enabled: bool,
     info_panel_component: InstanceDetails,
+    console_output_component: ConsoleOutput,
+    background_sender: UnboundedSender<BackgroundEvent>,
+    background_receiver: UnboundedReceiver<BackgroundEvent>,
+    filters_component: TextInput,
+    filters_enabled: bool,
+    adhoc_filters: Vec<String>,
+    current_region: Option<String>,
+    current_filters: Vec<String>,
+    error_panel_component: Option<ErrorPanel>,
+    multi_select: bool,
+    bookmark_list_component: BookmarkList,
+    bookmark_input_component: TextInput,
+    /// Instance being bookmarked while the alias is typed
+    bookmark_instance: Option<InstanceInfo>,
+    /// Connect as soon as the instances are loaded, if exactly one matches
+    auto_connect: bool,
+    /// Region and filters of the instances being fetched, results for others are dropped
+    instances_loading: Option<(String, Vec<String>)>,
+    recent_list_component: RecentList,
+    recent_search_component: TextInput,
+    recent_search_enabled: bool,
+    column_chooser_component: Option<ColumnChooser>,
+    /// Columns chosen in the column chooser without saving them, for the rest of the session
+    session_columns: Option<Vec<Column>>,
+    /// Profile to switch back to when leaving the region of a bookmark opened with its own profile
+    profile_before_bookmark: Option<Option<String>>,
+    /// Past connection found again, to reconnect to
+    revalidated_instance: Option<InstanceInfo>,
 }
 
 impl App {
-    pub fn new() -> Self {
-        let config = config::Config::new();
+    /// When `region` is given, the region selection is skipped.
+    /// `search` pre-fills the instance search.
+    /// Otherwise the recent connections are shown first, if there are any.
+    pub fn new(config: config::Config, region: Option<String>, search: Option<String>) -> Self {
         let mut region_select = RegionList::with_items(config.get_visible_regions());
         region_select.set_favorites(config.get_favorite_regions());
-        App {
+        let (background_sender, background_receiver) = unbounded_channel();
+        let mut app = App {
             config: config.clone(),
             search_component: TextInput::default(),
             region_select_component: region_select,
@@ -60,49 +126,228 @@ impl App {
             info_panel_enabled: false,
             info_panel_component: InstanceDetails::default(),
             search_enabled: false,
+            console_output_component: ConsoleOutput::default(),
+            background_sender,
+            background_receiver,
+            filters_component: TextInput::with_prompt("Server filters: "),
+            filters_enabled: false,
+            adhoc_filters: Vec::new(),
+            current_region: None,
+            current_filters: Vec::new(),
+            error_panel_component: None,
+            multi_select: false,
+            bookmark_list_component: BookmarkList::default(),
+            bookmark_input_component: TextInput::with_prompt("Bookmark as: "),
+            bookmark_instance: None,
+            auto_connect: false,
+            instances_loading: None,
+            recent_list_component: RecentList::with_items(load_recent()),
+            recent_search_component: TextInput::with_prompt("Filter: "),
+            recent_search_enabled: false,
+            column_chooser_component: None,
+            session_columns: None,
+            profile_before_bookmark: None,
+            revalidated_instance: None,
+        };
+        if let Some(search) = search {
+            app.search_component.set_value(search);
         }
+        if let Some(region) = region {
+            app.status = AppStatus::MainScreen;
+            app.select_region(region);
+        } else if app.search_component.get_value().is_empty()
+            && !app.recent_list_component.is_empty()
+        {
+            app.status = AppStatus::RecentState;
+        }
+        app
     }
 
-    pub async fn run(
-        &mut self,
-        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
-    ) -> Result<InstanceInfo> {
+    /// Let the user mark several instances, all returned by `run`
+    pub fn enable_multi_select(&mut self) {
+        self.multi_select = true;
+        self.instances_table_component.set_multi_select(true);
+    }
+
+    pub async fn run(&mut self, terminal: &mut Tui) -> Result<Vec<InstanceInfo>> {
         let mut should_exit = false;
-        let mut return_value: Option<InstanceInfo> = None;
+        let mut return_value: Option<Vec<InstanceInfo>> = None;
         loop {
             // render
             terminal
                 .draw(|frame| {
                     // Set global layout
                     let outer_layout = self.get_outer_layout(frame);
-                    let inner_layout = self.get_inner_layout(frame, &outer_layout);
+                    let inner_layout = self.get_inner_layout(&outer_layout);
                     
                     match self.status {
+                        AppStatus::RecentState => {
+                            self.recent_list_component.render(frame, outer_layout[1]);
+                            if self.recent_search_enabled {
+                                self.recent_search_component.render(frame, outer_layout[2]);
+                                frame.set_cursor(
+                                    outer_layout[2].x
+                                        + self.recent_search_component.get_cursor_position()
+                                            as u16,
+                                    outer_layout[2].y,
+                                );
+                            } else {
+                                self.recent_list_component.render_help(frame, outer_layout[2]);
+                            }
+                        }
+                        AppStatus::BookmarksState => {
+                            self.bookmark_list_component.render(frame, outer_layout[1]);
+                            self.bookmark_list_component
+                                .render_help(frame, outer_layout[2]);
+                        }
                         AppStatus::RegionSelectState => {
                             self.region_select_component.render(frame, inner_layout[0]);
-                            self.region_select_component.render_help(frame, outer_layout[2]);
+                            if self.filters_enabled {
+                                self.filters_component.render(frame, outer_layout[2]);
+                                frame.set_cursor(
+                                    outer_layout[2].x
+                                        + self.filters_component.get_cursor_position() as u16,
+                                    outer_layout[2].y,
+                                );
+                            } else {
+                                self.region_select_component.render_help(frame, outer_layout[2]);
+                            }
                         }
                         AppStatus::MainScreen => {
                             self.instances_table_component.render(frame, inner_layout[0]);
                             self.info_panel_component.render(frame, inner_layout[1]);
-                            if self.search_enabled {
+                            if let Some(chooser) = self.column_chooser_component.as_mut() {
+                                chooser.render(frame, inner_layout[0]);
+                                chooser.render_help(frame, outer_layout[2]);
+                            } else if self.search_enabled {
                                 self.search_component.render(frame, outer_layout[2]);
                                 frame.set_cursor(
                                     outer_layout[2].x
                                         + self.search_component.get_cursor_position() as u16,
                                     outer_layout[2].y,
                                 );
+                            } else if self.bookmark_instance.is_some() {
+                                self.bookmark_input_component.render(frame, outer_layout[2]);
+                                frame.set_cursor(
+                                    outer_layout[2].x
+                                        + self.bookmark_input_component.get_cursor_position()
+                                            as u16,
+                                    outer_layout[2].y,
+                                );
                             } else {
                                 self.instances_table_component.render_help(frame, outer_layout[2])
                             }
                         }
+                        AppStatus::ConsoleOutputState => {
+                            self.console_output_component.render(frame, outer_layout[1]);
+                            self.console_output_component
+                                .render_help(frame, outer_layout[2]);
+                        }
+                        AppStatus::ErrorState => {
+                            if let Some(error_panel) = self.error_panel_component.as_mut() {
+                                error_panel.render(frame, outer_layout[1]);
+                                error_panel.render_help(frame, outer_layout[2]);
+                            }
+                        }
                     }
                 })
                 .unwrap();
 
+            self.handle_background_events();
+            if let Some(instance) = self.take_auto_connect() {
+                return Ok(vec![instance]);
+            }
+            if let Some(instance) = self.revalidated_instance.take() {
+                return Ok(vec![instance]);
+            }
+            if let AppStatus::MainScreen = self.status {
+                if self.info_panel_enabled {
+                    self.request_metrics();
+                }
+            }
+
             // handle events
+            if !event::poll(Duration::from_millis(100))? {
+                continue;
+            }
             let event = event::read().unwrap();
+            self.auto_connect = false;
             match self.status {
+                AppStatus::RecentState if self.recent_search_enabled => {
+                    let action = self.recent_search_component.handle_action(event);
+                    match action {
+                        Action::Exit | Action::Return(_) => {
+                            self.recent_search_enabled = false;
+                        }
+                        Action::PartialReturn(filter) => {
+                            self.recent_list_component.apply_filter(filter);
+                        }
+                        Action::ReturnWithKey(key) => {
+                            match key {
+                                event::KeyCode::Up => {
+                                    self.recent_list_component.previous();
+                                }
+                                event::KeyCode::Down => {
+                                    self.recent_list_component.next();
+                                }
+                                _ => {}
+                            }
+                            self.recent_search_enabled = false;
+                        }
+                        _ => {}
+                    }
+                }
+                AppStatus::RecentState => {
+                    let action = self.recent_list_component.handle_action(event);
+                    match action {
+                        Action::Exit => {
+                            should_exit = true;
+                        }
+                        Action::ShowRegions => {
+                            self.status = AppStatus::RegionSelectState;
+                        }
+                        Action::Search => {
+                            self.recent_search_enabled = true;
+                        }
+                        Action::Reconnect(entry) => {
+                            self.revalidate(entry);
+                        }
+                        _ => {}
+                    }
+                }
+                AppStatus::BookmarksState => {
+                    let action = self.bookmark_list_component.handle_action(event);
+                    match action {
+                        Action::Exit => {
+                            self.status = AppStatus::RegionSelectState;
+                        }
+                        Action::Return(alias) => {
+                            self.open_bookmark(&alias);
+                        }
+                        Action::Remove(alias) => {
+                            let status = match self.config.remove_bookmark(&alias) {
+                                Ok(_) => String::default(),
+                                Err(e) => format!("Failed to remove the bookmark: {:#}", e),
+                            };
+                            self.bookmark_list_component.set_status(status);
+                            self.bookmark_list_component
+                                .update_items(self.config.get_bookmarks());
+                        }
+                        _ => {}
+                    }
+                }
+                AppStatus::RegionSelectState if self.filters_enabled => {
+                    let action = self.filters_component.handle_action(event);
+                    match action {
+                        Action::Exit => {
+                            self.filters_enabled = false;
+                        }
+                        Action::Return(filters) => {
+                            self.set_adhoc_filters(filters);
+                        }
+                        _ => {}
+                    }
+                }
                 AppStatus::RegionSelectState => {
                     let action = self.region_select_component.handle_action(event);
                     match action {
@@ -111,34 +356,59 @@ impl App {
                         }
                         Action::Return(region) => {
                             self.status = AppStatus::MainScreen;
-                            let mut instances = fetch_instances(Region::new(region)).await?;
-                            instances
-                                .sort_by_key(|instance_info| instance_info.get_name().to_owned());
-                            let search_input = self.search_component.get_value();
-
-                            self.instances_table_component = InstanceTable::with_items_and_filter(instances, search_input);
-                            
+                            self.select_region(region);
                         }
                         Action::Hide(region) => {
-                            self.config.hide_region(region);
+                            let saved = self.config.hide_region(region);
+                            self.show_save_error(saved);
                             self.region_select_component
                                 .update_items(self.config.get_visible_regions());
                         }
                         Action::Reset => {
-                            self.config.reset_hidden_regions();
+                            let saved = self.config.reset_hidden_regions();
+                            self.show_save_error(saved);
                             self.region_select_component
                                 .update_items(self.config.get_visible_regions());
                         }
                         Action::ToggleFavorite(region) => {
-                            self.config.toggle_favorite_region(region);
+                            let saved = self.config.toggle_favorite_region(region);
+                            self.show_save_error(saved);
                             self.region_select_component
                                 .set_favorites(self.config.get_favorite_regions());
                         }
+                        Action::EditFilters => {
+                            self.filters_enabled = true;
+                        }
+                        Action::ShowBookmarks => {
+                            self.bookmark_list_component
+                                .update_items(self.config.get_bookmarks());
+                            self.bookmark_list_component.set_status(String::default());
+                            self.status = AppStatus::BookmarksState;
+                        }
+                        Action::ShowRecent => {
+                            self.recent_list_component.update_items(load_recent());
+                            self.status = AppStatus::RecentState;
+                        }
                         _ => {}
                     }
                 }
                 AppStatus::MainScreen => {
-                    if self.search_enabled {
+                    if let Some(chooser) = self.column_chooser_component.as_mut() {
+                        let action = chooser.handle_action(event);
+                        let columns = chooser.get_columns();
+                        self.instances_table_component.set_columns(columns.clone());
+                        match action {
+                            Action::Exit => {
+                                self.session_columns = Some(columns);
+                                self.column_chooser_component = None;
+                            }
+                            Action::Return(_) => {
+                                self.save_columns(columns);
+                                self.column_chooser_component = None;
+                            }
+                            _ => {}
+                        }
+                    } else if self.search_enabled {
                         let action = self.search_component.handle_action(event);
                         match action {
                             Action::Exit => {
@@ -165,16 +435,31 @@ impl App {
                             }
                             _ => {}
                         }
+                    } else if self.bookmark_instance.is_some() {
+                        let action = self.bookmark_input_component.handle_action(event);
+                        match action {
+                            Action::Exit => {
+                                self.bookmark_instance = None;
+                            }
+                            Action::Return(alias) => {
+                                self.add_bookmark(alias);
+                            }
+                            _ => {}
+                        }
                     } else {
                         let action = self
                             .instances_table_component.handle_action(event);
                         match action {
                             Action::Exit => {
-                                self.status = AppStatus::RegionSelectState;
+                                self.leave_region();
                             }
                             Action::ReturnInstance(instance) => {
                                 should_exit = true;
-                                return_value = Some(instance);
+                                return_value = Some(vec![instance]);
+                            }
+                            Action::ReturnInstances(instances) => {
+                                should_exit = true;
+                                return_value = Some(instances);
                             }
                             Action::Search => {
                                 self.search_enabled = true;
@@ -185,10 +470,95 @@ impl App {
                             Action::Select(instance) => {
                                 self.info_panel_component.set_instance(instance);
                             }
+                            Action::Refresh => {
+                                self.instances_table_component
+                                    .set_status("Refreshing…".to_string());
+                                self.request_instances();
+                            }
+                            Action::Bookmark(instance) => {
+                                let alias: String = match instance.get_name() {
+                                    name if name.is_empty() => instance.get_instance_id(),
+                                    name => name.replace(char::is_whitespace, "-"),
+                                };
+                                self.bookmark_input_component.set_value(alias);
+                                self.bookmark_instance = Some(instance);
+                            }
+                            Action::ChooseColumns => {
+                                let fields = FIELDS
+                                    .iter()
+                                    .map(|field| field.to_string())
+                                    .chain(
+                                        self.instances_table_component
+                                            .get_tag_keys()
+                                            .iter()
+                                            .map(|key| format!("tag:{}", key)),
+                                    )
+                                    .collect();
+                                self.column_chooser_component = Some(ColumnChooser::new(
+                                    self.instances_table_component.get_columns(),
+                                    fields,
+                                ));
+                            }
+                            Action::Sort(sort) => {
+                                // The order is only a convenience, failing to remember it is shown
+                                // without interrupting the user
+                                if let Some(region) = self.current_region.clone() {
+                                    if let Err(e) = save_sort(&region, &sort) {
+                                        self.instances_table_component.set_status(format!(
+                                            "Failed to remember the sort order: {:#}",
+                                            e
+                                        ));
+                                    }
+                                }
+                            }
+                            Action::ShowConsole(instance) => {
+                                self.status = AppStatus::ConsoleOutputState;
+                                self.console_output_component.set_instance(instance);
+                                self.load_console_output(false).await;
+                            }
                             _ => {}
                         }
                     }
                 }
+                AppStatus::ErrorState => {
+                    let action = match self.error_panel_component.as_mut() {
+                        Some(error_panel) => error_panel.handle_action(event),
+                        None => Action::Exit,
+                    };
+                    match action {
+                        Action::Exit => {
+                            self.error_panel_component = None;
+                            self.leave_region();
+                        }
+                        Action::Refresh => {
+                            self.error_panel_component = None;
+                            self.status = AppStatus::MainScreen;
+                            self.instances_table_component
+                                .set_status("Retrying…".to_string());
+                            self.request_instances();
+                        }
+                        Action::Return(_) => {
+                            self.error_panel_component = None;
+                            self.status = AppStatus::MainScreen;
+                        }
+                        _ => {}
+                    }
+                }
+                AppStatus::ConsoleOutputState => {
+                    let action = self.console_output_component.handle_action(event);
+                    match action {
+                        Action::Exit => {
+                            self.status = AppStatus::MainScreen;
+                        }
+                        Action::Refresh => {
+                            self.load_console_output(true).await;
+                        }
+                        Action::Screenshot => {
+                            self.save_console_screenshot().await;
+                        }
+                        _ => {}
+                    }
+                }
             }
 
             if should_exit {
@@ -196,11 +566,325 @@ impl App {
             }
         }
         match return_value {
-            Some(instance) => Ok(instance),
+            Some(instances) => Ok(instances),
             None => Err(RuntimeError::UserExit.into()),
         }
     }
 
+    /// Show the instances of a region, from the cache if possible.
+    /// Stale or missing inventories are refreshed in the background.
+    fn select_region(&mut self, region: String) {
+        let profile = current_profile();
+        let mut filters = self.config.get_instance_filters(&region, &profile);
+        filters.extend(self.adhoc_filters.clone());
+        self.current_region = Some(region.clone());
+        self.current_filters = filters.clone();
+
+        let search_input = self.search_component.get_value();
+        let columns = self
+            .session_columns
+            .clone()
+            .unwrap_or_else(|| self.config.get_columns(&profile));
+        let sort = load_sort(&region);
+        // A fetch still running for the previous region doesn't hold up this one
+        self.instances_loading = None;
+        match load_inventory(&profile, &region, &filters) {
+            Some(inventory) => {
+                self.instances_table_component =
+                    InstanceTable::with_items_and_filter(inventory.instances, search_input, sort);
+                self.instances_table_component
+                    .set_multi_select(self.multi_select);
+                self.instances_table_component.set_columns(columns);
+                if inventory.age >= self.config.get_cache_ttl() {
+                    self.instances_table_component
+                        .set_status("Stale, refreshing…".to_string());
+                    self.request_instances();
+                }
+            }
+            None => {
+                self.instances_table_component =
+                    InstanceTable::with_items_and_filter(vec![], search_input, sort);
+                self.instances_table_component
+                    .set_multi_select(self.multi_select);
+                self.instances_table_component.set_columns(columns);
+                self.instances_table_component
+                    .set_status("Loading instances…".to_string());
+                self.request_instances();
+            }
+        }
+    }
+
+    /// Open the region of a bookmark with its profile, connecting once its instance is found
+    fn open_bookmark(&mut self, alias: &str) {
+        let Some(bookmark) = self.config.get_bookmark(alias) else {
+            return;
+        };
+        if bookmark.profile.is_some() && bookmark.profile != profile_override() {
+            self.profile_before_bookmark.get_or_insert_with(profile_override);
+            set_profile(bookmark.profile.clone());
+        }
+        self.search_component.set_value(bookmark.get_query());
+        self.status = AppStatus::MainScreen;
+        self.auto_connect = true;
+        self.select_region(bookmark.region);
+    }
+
+    /// Go back to the region screen, with the profile used before opening a bookmark
+    fn leave_region(&mut self) {
+        self.status = AppStatus::RegionSelectState;
+        if let Some(profile) = self.profile_before_bookmark.take() {
+            set_profile(profile);
+            // Instances still loading with the bookmark's profile are dropped
+            self.current_region = None;
+        }
+    }
+
+    /// Look up the instance of a past connection with its profile, as it may have been stopped or
+    /// replaced since. The session's profile is only switched once the instance is found.
+    fn revalidate(&mut self, entry: HistoryEntry) {
+        let profile = if entry.profile == current_profile() {
+            profile_override()
+        } else {
+            Some(entry.profile.clone())
+        };
+        self.recent_list_component.set_status(format!("Checking {}…", entry.instance_id));
+        let sender = self.background_sender.clone();
+        tokio::spawn(async move {
+            let filters = vec![format!("instance-id={}", entry.instance_id)];
+            let region = Region::new(entry.region.clone());
+            let instances = fetch_profile_instances(profile.clone(), region, &filters).await;
+            let _ = sender.send(BackgroundEvent::Revalidated {
+                entry,
+                profile,
+                instances,
+            });
+        });
+    }
+
+    /// Reconnect to a past connection that was found again. The reason is shown on the recent
+    /// screen when it can't be connected to.
+    fn update_revalidated(
+        &mut self,
+        entry: HistoryEntry,
+        profile: Option<String>,
+        instances: Result<Vec<InstanceInfo>>,
+    ) {
+        // The user has moved on meanwhile
+        if !matches!(self.status, AppStatus::RecentState) {
+            return;
+        }
+        match instances {
+            Ok(mut instances) if !instances.is_empty() => {
+                set_profile(profile);
+                self.revalidated_instance = Some(instances.remove(0));
+            }
+            Ok(_) => {
+                self.recent_list_component.set_status(format!(
+                    "{} ({}) no longer exists or isn't running",
+                    entry.name, entry.instance_id
+                ));
+            }
+            Err(e) => {
+                self.recent_list_component
+                    .set_status(format!("Failed to check {}: {:#}", entry.instance_id, e));
+            }
+        }
+    }
+
+    /// Save the columns chosen in the column chooser for the current profile
+    fn save_columns(&mut self, columns: Vec<Column>) {
+        let profile = current_profile();
+        let status = match self.config.set_columns(&profile, &columns) {
+            Ok(_) => {
+                self.session_columns = None;
+                format!("Saved the columns for profile {}", profile)
+            }
+            Err(e) => {
+                self.session_columns = Some(columns);
+                format!("Failed to save the columns: {:#}", e)
+            }
+        };
+        self.instances_table_component.set_status(status);
+    }
+
+    /// Save the instance being bookmarked under the typed alias
+    fn add_bookmark(&mut self, alias: String) {
+        let alias = alias.trim().trim_start_matches('@').to_string();
+        let (Some(instance), Some(region)) =
+            (self.bookmark_instance.take(), self.current_region.clone())
+        else {
+            return;
+        };
+        if alias.is_empty() {
+            return;
+        }
+        let bookmark = Bookmark {
+            region,
+            profile: profile_override(),
+            instance_id: Some(instance.get_instance_id()),
+            query: None,
+        };
+        let status = match self.config.add_bookmark(&alias, &bookmark) {
+            Ok(_) => format!("Bookmarked as @{}", alias),
+            Err(e) => format!("Failed to save the bookmark: {:#}", e),
+        };
+        self.instances_table_component.set_status(status);
+    }
+
+    /// The instance to connect to when a bookmark was opened and its region finished loading
+    fn take_auto_connect(&mut self) -> Option<InstanceInfo> {
+        let loading_current = self.instances_loading.as_ref().is_some_and(|(region, filters)| {
+            self.current_region.as_ref() == Some(region) && &self.current_filters == filters
+        });
+        if !self.auto_connect || loading_current {
+            return None;
+        }
+        if let AppStatus::MainScreen = self.status {
+            self.auto_connect = false;
+            return self.instances_table_component.single_visible();
+        }
+        None
+    }
+
+    fn request_instances(&mut self) {
+        let Some(region) = self.current_region.clone() else {
+            return;
+        };
+        let filters = self.current_filters.clone();
+        self.instances_loading = Some((region.clone(), filters.clone()));
+        let sender = self.background_sender.clone();
+        tokio::spawn(async move {
+            let instances = fetch_instances(Region::new(region.clone()), &filters).await;
+            let _ = sender.send(BackgroundEvent::Instances {
+                region,
+                filters,
+                instances,
+            });
+        });
+    }
+
+    fn update_instances(
+        &mut self,
+        region: String,
+        filters: Vec<String>,
+        instances: Result<Vec<InstanceInfo>>,
+    ) {
+        if self.instances_loading.as_ref() == Some(&(region.clone(), filters.clone())) {
+            self.instances_loading = None;
+        }
+        // Results for a region or filters the user has since moved away from are dropped
+        if self.current_region.as_ref() != Some(&region) || self.current_filters != filters {
+            return;
+        }
+        match instances {
+            Ok(instances) => {
+                // The cache is an optimization, failing to write it shouldn't interrupt the user
+                let _ = save_inventory(&current_profile(), &region, &filters, &instances);
+                self.instances_table_component.update_items(instances);
+                self.instances_table_component.set_status(String::default());
+            }
+            Err(e) => {
+                self.instances_table_component
+                    .set_status(format!("Failed to fetch instances: {:#}", e));
+                if let AppStatus::MainScreen = self.status {
+                    let has_cached_instances = !self.instances_table_component.is_empty();
+                    self.error_panel_component = Some(ErrorPanel::new(&e, has_cached_instances));
+                    self.status = AppStatus::ErrorState;
+                }
+            }
+        }
+    }
+
+    /// Region settings still apply to the session when they can't be saved, so failing to save
+    /// is only shown, as is a change refused because another config layer sets the value
+    fn show_save_error(&mut self, saved: Result<()>) {
+        if let Err(e) = saved {
+            self.region_select_component.set_title(format!("{:#}", e));
+        }
+    }
+
+    /// Validate and apply the server-side filters typed by the user, keeping the input open on errors
+    fn set_adhoc_filters(&mut self, filters: String) {
+        let filters = split_filters(&filters);
+        if let Some(e) = filters.iter().find_map(|f| parse_filter(f).err()) {
+            self.region_select_component.set_title(e.to_string());
+            return;
+        }
+        self.filters_enabled = false;
+        let title = if filters.is_empty() {
+            String::default()
+        } else {
+            format!("Server filters: {}", filters.join(" "))
+        };
+        self.region_select_component.set_title(title);
+        self.adhoc_filters = filters;
+    }
+
+    fn request_metrics(&mut self) {
+        let Some(instance) = self.info_panel_component.start_metrics_fetch() else {
+            return;
+        };
+        let sender = self.background_sender.clone();
+        tokio::spawn(async move {
+            let metrics = fetch_instance_metrics(&instance).await;
+            let instance_id = instance.get_instance_id();
+            let _ = sender.send(BackgroundEvent::Metrics(instance_id, metrics));
+        });
+    }
+
+    fn handle_background_events(&mut self) {
+        while let Ok(event) = self.background_receiver.try_recv() {
+            match event {
+                BackgroundEvent::Metrics(instance_id, metrics) => {
+                    self.info_panel_component.set_metrics(instance_id, metrics);
+                }
+                BackgroundEvent::Instances {
+                    region,
+                    filters,
+                    instances,
+                } => {
+                    self.update_instances(region, filters, instances);
+                }
+                BackgroundEvent::Revalidated {
+                    entry,
+                    profile,
+                    instances,
+                } => {
+                    self.update_revalidated(entry, profile, instances);
+                }
+            }
+        }
+    }
+
+    async fn load_console_output(&mut self, latest: bool) {
+        let Some(instance) = self.console_output_component.get_instance() else {
+            return;
+        };
+        match fetch_console_output(&instance, latest).await {
+            Ok(output) => self.console_output_component.set_output(output),
+            Err(e) => self
+                .console_output_component
+                .set_status(format!("Failed to fetch console output: {:#}", e)),
+        }
+    }
+
+    async fn save_console_screenshot(&mut self) {
+        let Some(instance) = self.console_output_component.get_instance() else {
+            return;
+        };
+        let status = match fetch_console_screenshot(&instance).await {
+            Ok(image) => {
+                let file_name = format!("{}-screenshot.jpg", instance.get_instance_id());
+                match std::fs::write(&file_name, image) {
+                    Ok(_) => format!("Saved screenshot to {}", file_name),
+                    Err(e) => format!("Failed to save {}: {}", file_name, e),
+                }
+            }
+            Err(e) => format!("Failed to fetch screenshot: {:#}", e),
+        };
+        self.console_output_component.set_status(status);
+    }
+
     fn get_outer_layout(&self, frame: &mut Frame) -> Rc<[Rect]> {
         let outer = Layout::default()
         .direction(Direction::Vertical)
@@ -215,28 +899,67 @@ impl App {
         )
         .split(frame.size());
 
-        let tabs = Tabs::new(vec!["Region", "Instances", "Connection"])
+        let tabs = Tabs::new(vec!["Recent", "Bookmarks", "Region", "Instances", "Connection"])
             .block(Block::bordered())
-            .style(Style::default().white())
-            .highlight_style(Style::default().yellow())
+            .style(get_theme().text())
+            .highlight_style(get_theme().active_tab())
             .select(match self.status {
-                AppStatus::RegionSelectState => 0,
-                AppStatus::MainScreen => 1,
+                AppStatus::RecentState => 0,
+                AppStatus::BookmarksState => 1,
+                AppStatus::RegionSelectState => 2,
+                AppStatus::MainScreen
+                | AppStatus::ConsoleOutputState
+                | AppStatus::ErrorState => 3,
             });
         //.divider(symbols::DOT);
         frame.render_widget(tabs, outer[0]);
         outer
     }
 
-    fn get_inner_layout(&self, frame: &mut Frame, outer_layout: &Rc<[Rect]>) -> Rc<[Rect]> {
-        let inner_layout = Layout::default()
+    fn get_inner_layout(&self, outer_layout: &Rc<[Rect]>) -> Rc<[Rect]> {
+        Layout::default()
             .direction(Direction::Horizontal)
             .constraints(if self.info_panel_enabled {
                 vec![Constraint::Percentage(75), Constraint::Percentage(25)]
             } else {
                 vec![Constraint::Percentage(100), Constraint::Percentage(0)]
             })
-            .split(outer_layout[1]);
-        inner_layout
+            .split(outer_layout[1])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_ec2::types::Instance;
+
+    fn showing_instance(region: &str) -> App {
+        let mut app = App::new(config::Config::default(), None, None);
+        let instance = Instance::builder().instance_id("i-0123").build();
+        let instance = InstanceInfo::new(Region::new(region.to_string()), instance);
+        app.instances_table_component = InstanceTable::with_items(vec![instance]);
+        app.current_region = Some(region.to_string());
+        app.status = AppStatus::MainScreen;
+        app.auto_connect = true;
+        app
+    }
+
+    #[test]
+    fn auto_connect_waits_for_the_fetch_of_the_current_region() {
+        let mut app = showing_instance("eu-west-1");
+        app.instances_loading = Some(("eu-west-1".to_string(), vec![]));
+        assert!(app.take_auto_connect().is_none());
+    }
+
+    #[test]
+    fn a_dropped_fetch_of_another_region_doesnt_block_auto_connect() {
+        let mut app = showing_instance("eu-west-1");
+        app.instances_loading = Some(("us-east-1".to_string(), vec![]));
+        assert!(app.take_auto_connect().is_some());
+
+        app.auto_connect = true;
+        app.update_instances("us-east-1".to_string(), vec![], Err(anyhow::anyhow!("timed out")));
+        assert!(app.instances_loading.is_none());
+        assert!(app.take_auto_connect().is_some());
     }
 }
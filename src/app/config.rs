@@ -1,12 +1,61 @@
-use anyhow::Result;
+use crate::aws::{split_filters, AwsSettings};
+use crate::dirs::{get_config_dir, write_atomically};
+use crate::output::validate_fields;
+use anyhow::{bail, Context, Result};
 use configparser::ini::Ini;
 use home::home_dir;
-use std::path::PathBuf;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use toml::{Table, Value};
+
+mod settings;
+use settings::string_or_list;
+pub use settings::{Bookmark, Column, Palette, Settings, ThemeSection, Tunnel, DEFAULT_COLUMNS};
+
+/// Where an effective config value comes from, by increasing precedence
+#[derive(Debug, Clone, PartialEq)]
+pub enum Origin {
+    Default,
+    File(PathBuf),
+    Env(String),
+    Flag(String),
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::File(path) => write!(f, "{}", path.to_string_lossy()),
+            Origin::Env(name) => write!(f, "environment variable {}", name),
+            Origin::Flag(flag) => write!(f, "command line flag {}", flag),
+        }
+    }
+}
+
+/// The config is layered: defaults, then the system file, the user file, a project-local
+/// `.sm_connect.toml`, `SM_CONNECT_<SECTION>_<KEY>` environment variables and command line flags.
+/// Changes made in the UI are saved to the user file.
 #[derive(Default, Debug, Clone)]
-pub(super) struct Config {
-    internal: Ini,
+pub struct Config {
+    path: Option<PathBuf>,
+    /// Every layer by increasing precedence, the user file among them
+    layers: Vec<(Origin, Table)>,
+    user_layer: usize,
+    merged: Table,
+    settings: Settings,
+    /// Layer of every effective value, by key path as written in TOML
+    origins: BTreeMap<String, Origin>,
 }
 
+const SYSTEM_CONFIG_PATH: &str = "/etc/sm_connect.toml";
+
+const PROJECT_CONFIG_NAME: &str = ".sm_connect.toml";
+
+const ENV_PREFIX: &str = "SM_CONNECT_";
+
 const DEFAULT_REGIONS: &[&str] = &[
     "af-south-1",
     "ap-east-1",
@@ -40,117 +89,903 @@ const DEFAULT_REGIONS: &[&str] = &[
 ];
 
 impl Config {
-    pub fn new() -> Config {
-        let Ok(config_path) = Config::get_config_path() else {
-            return Config {
-                internal: Ini::new(),
-            };
+    /// Load every layer, reading the user file from `path`, or from the default location when
+    /// not given
+    pub fn new(path: Option<PathBuf>) -> Result<Config> {
+        let mut config = Config {
+            path: path.clone().or_else(|| Config::get_config_path().ok()),
+            ..Config::default()
         };
+        if let (None, Some(default_path), Some(legacy_path)) =
+            (path, &config.path, Config::get_legacy_config_path())
+        {
+            Config::migrate_legacy_file(&legacy_path, default_path)?;
+        }
+        let defaults = Value::try_from(Settings::default())?;
+        config.layers.push((
+            Origin::Default,
+            defaults.as_table().cloned().unwrap_or_default(),
+        ));
 
-        if config_path.exists() {
-            let mut config = Ini::new();
-            config.load(config_path).unwrap();
-            Config { internal: config }
+        let system_path = PathBuf::from(SYSTEM_CONFIG_PATH);
+        if let Some(table) = Config::read_toml_file(&system_path)? {
+            config.layers.push((Origin::File(system_path), table));
+        }
+
+        config.user_layer = config.layers.len();
+        let user_table = match config.path {
+            Some(ref path) => Config::read_user_file(path)?,
+            None => Table::new(),
+        };
+        config.layers.push((
+            Origin::File(config.path.clone().unwrap_or_default()),
+            user_table,
+        ));
+
+        if let Some(project_path) = Config::find_project_config() {
+            if let Some(table) = Config::read_toml_file(&project_path)? {
+                config.layers.push((Origin::File(project_path), table));
+            }
+        }
+
+        for (name, value) in std::env::vars() {
+            let Some(key) = name.strip_prefix(ENV_PREFIX) else {
+                continue;
+            };
+            if let Some((section, key)) = key.to_lowercase().split_once('_') {
+                config.layers.push((
+                    Origin::Env(name.clone()),
+                    Config::parse_env_value(section, key, &value),
+                ));
+            }
+        }
+
+        config.merge()?;
+        Ok(config)
+    }
+
+    /// Numbers and lists are parsed as TOML, anything else is a string. So is a value whose TOML
+    /// type doesn't fit the setting, e.g. a profile named `123`.
+    fn parse_env_value(section: &str, key: &str, value: &str) -> Table {
+        let string = Config::single(section, key, Value::String(value.to_string()));
+        let Some(typed) = format!("value = {}", value)
+            .parse::<Table>()
+            .ok()
+            .and_then(|mut table| table.remove("value"))
+        else {
+            return string;
+        };
+        let typed = Config::single(section, key, typed);
+        let fits = |table: &Table| Value::Table(table.clone()).try_into::<Settings>().is_ok();
+        if !fits(&typed) && fits(&string) {
+            string
         } else {
-            Config::default()
+            typed
+        }
+    }
+
+    fn single(section: &str, key: &str, value: Value) -> Table {
+        let mut keys = Table::new();
+        keys.insert(key.to_string(), value);
+        let mut table = Table::new();
+        table.insert(section.to_string(), Value::Table(keys));
+        table
+    }
+
+    fn read_toml_file(path: &Path) -> Result<Option<Table>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let read_error = || format!("failed to read {}", path.to_string_lossy());
+        let content = std::fs::read_to_string(path).with_context(read_error)?;
+        Ok(Some(content.parse().with_context(read_error)?))
+    }
+
+    /// Read the user file, migrating it first when it still has the INI format of older versions
+    fn read_user_file(path: &Path) -> Result<Table> {
+        Ok(Config::read_toml_file(path)?.unwrap_or_default())
+    }
+
+    /// Older versions kept the user file in `~/.sm_connect`, possibly in the INI format. When
+    /// `path` doesn't exist yet, the legacy file is converted to TOML and written there, and the
+    /// legacy file is left as it was.
+    fn migrate_legacy_file(legacy_path: &Path, path: &Path) -> Result<()> {
+        if path.exists() || !legacy_path.exists() {
+            return Ok(());
         }
+        let read_error = || format!("failed to read {}", legacy_path.to_string_lossy());
+        let content = std::fs::read_to_string(legacy_path).with_context(read_error)?;
+        let table = Config::parse_legacy_file(&content).with_context(read_error)?;
+        let migrate_error = || {
+            format!(
+                "failed to migrate {} to {}",
+                legacy_path.to_string_lossy(),
+                path.to_string_lossy()
+            )
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).with_context(migrate_error)?;
+        }
+        write_atomically(path, &toml::to_string_pretty(&table)?).with_context(migrate_error)?;
+        // Keeps the file private if the legacy one was
+        let permissions = std::fs::metadata(legacy_path)?.permissions();
+        std::fs::set_permissions(path, permissions).with_context(migrate_error)?;
+        eprintln!(
+            "Migrated {} to {}, the old file is no longer read",
+            legacy_path.to_string_lossy(),
+            path.to_string_lossy()
+        );
+        Ok(())
+    }
+
+    /// Parse the legacy `~/.sm_connect`, which can be TOML or INI. It is only read as INI when
+    /// nothing in it is TOML syntax: the INI parser accepts almost anything, so a TOML file with a
+    /// typo would otherwise be mangled by the migration rather than reported.
+    fn parse_legacy_file(content: &str) -> Result<Table> {
+        let toml_error = match content.parse::<Table>() {
+            Ok(table) => return Ok(table),
+            Err(e) => e,
+        };
+        if Config::has_toml_syntax(content) {
+            return Err(toml_error.into());
+        }
+        let mut ini = Ini::new();
+        if ini.read(content.to_string()).is_err() {
+            return Err(toml_error.into());
+        }
+        Ok(Config::migrate_ini(&ini))
+    }
+
+    /// Whether any line has syntax the INI format of older versions never had: dotted or quoted
+    /// table headers, arrays of tables, or quoted, array and inline table values
+    fn has_toml_syntax(content: &str) -> bool {
+        content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with(['#', ';']))
+            .any(|line| {
+                if let Some(header) = line.strip_prefix('[') {
+                    return header.contains(['[', '.', '"', '\'']);
+                }
+                line.split_once('=')
+                    .is_some_and(|(_, value)| value.trim_start().starts_with(['"', '\'', '[', '{']))
+            })
     }
 
-    pub fn persist(&self) {
-        let Ok(config_path) = Config::get_config_path() else {
+    /// Translate the INI config of older versions, where lists were comma separated strings
+    fn migrate_ini(ini: &Ini) -> Table {
+        let mut table = Table::new();
+        let list = |value: &str| {
+            let mut items: Vec<Value> = Vec::new();
+            for item in value
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+            {
+                if !items.iter().any(|existing| existing.as_str() == Some(item)) {
+                    items.push(Value::String(item.to_string()));
+                }
+            }
+            Value::Array(items)
+        };
+        let filters = |value: &str| {
+            Value::Array(
+                split_filters(value)
+                    .into_iter()
+                    .map(Value::String)
+                    .collect(),
+            )
+        };
+        for (section, keys) in ini.get_map_ref() {
+            for (key, value) in keys {
+                let value = value.clone().unwrap_or_default();
+                let path: Vec<&str> = match (section.as_str(), key.as_str()) {
+                    ("filters", "default") => vec!["filters", "default"],
+                    ("filters", key) => match key.split_once('.') {
+                        Some(("profile", profile)) => vec!["profiles", profile, "filters"],
+                        Some(("region", region)) => vec!["regions", "filters", region],
+                        _ => vec!["filters", key],
+                    },
+                    (section, key) => vec![section, key],
+                };
+                let value = match (section.as_str(), key.as_str()) {
+                    ("regions", "hidden" | "favorite") => list(&value),
+                    ("filters", _) => filters(&value),
+                    (_, _) => match value.trim().parse::<i64>() {
+                        Ok(number) => Value::Integer(number),
+                        Err(_) => Value::String(value),
+                    },
+                };
+                Config::insert(&mut table, &path, value);
+            }
+        }
+        table
+    }
+
+    fn insert(table: &mut Table, path: &[&str], value: Value) {
+        let Some((key, parents)) = path.split_last() else {
             return;
         };
+        let mut table = table;
+        for parent in parents {
+            let entry = table
+                .entry(parent.to_string())
+                .or_insert_with(|| Value::Table(Table::new()));
+            if !entry.is_table() {
+                *entry = Value::Table(Table::new());
+            }
+            let Value::Table(nested) = entry else {
+                return;
+            };
+            table = nested;
+        }
+        table.insert(key.to_string(), value);
+    }
+
+    /// Every value of a table with its key path, lists and empty tables count as values.
+    /// Paths are kept as keys rather than joined, as keys such as bookmark aliases can have dots.
+    fn leaves(table: &Table, prefix: &[String]) -> Vec<(Vec<String>, Value)> {
+        table
+            .iter()
+            .flat_map(|(key, value)| {
+                let path: Vec<String> = prefix.iter().chain([key]).cloned().collect();
+                match value {
+                    Value::Table(nested) if !nested.is_empty() && !Config::is_entry(&path) => {
+                        Config::leaves(nested, &path)
+                    }
+                    value => vec![(path, value.clone())],
+                }
+            })
+            .collect()
+    }
 
-        self.internal.write(config_path).unwrap();
+    /// Bookmarks are replaced whole by a later layer, rather than mixing the fields of bookmarks
+    /// with the same alias
+    fn is_entry(path: &[String]) -> bool {
+        matches!(path, [section, _] if section == "bookmarks")
     }
 
-    fn get_config_path() -> Result<PathBuf> {
-        let Some(home_dir) = home_dir() else {
-            return Result::Err(anyhow::anyhow!("Could not find home directory"));
+    /// A key path as written in TOML, e.g. `bookmarks."api.prod".region`
+    fn format_path(path: &[String]) -> String {
+        path.iter()
+            .map(|key| {
+                let is_bare = !key.is_empty()
+                    && key
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+                if is_bare {
+                    key.clone()
+                } else {
+                    Value::String(key.clone()).to_string()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join(".")
+    }
+
+    /// Combine the layers, later ones overriding earlier ones value by value
+    fn merge(&mut self) -> Result<()> {
+        let mut merged = Table::new();
+        self.origins.clear();
+        for (origin, table) in &self.layers {
+            for (path, value) in Config::leaves(table, &[]) {
+                let keys: Vec<&str> = path.iter().map(String::as_str).collect();
+                Config::insert(&mut merged, &keys, value);
+                self.origins
+                    .insert(Config::format_path(&path), origin.clone());
+            }
+        }
+        let settings: Settings = Value::Table(merged.clone())
+            .try_into()
+            .context("invalid configuration")?;
+        let columns = std::iter::once(&settings.columns.default)
+            .chain(settings.profiles.values().map(|profile| &profile.columns));
+        for columns in columns {
+            let fields: Vec<String> = columns.iter().map(|column| column.field.clone()).collect();
+            validate_fields(&fields).context("invalid configuration")?;
+        }
+        self.settings = settings;
+        self.merged = merged;
+        Ok(())
+    }
+
+    /// The config files that were read, by increasing precedence
+    pub fn get_files(&self) -> Vec<PathBuf> {
+        self.layers
+            .iter()
+            .filter_map(|(origin, _)| match origin {
+                Origin::File(path) if path.exists() => Some(path.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Override a value from a command line flag
+    pub fn set_flag(&mut self, section: &str, key: &str, value: &str, flag: &str) -> Result<()> {
+        self.layers.push((
+            Origin::Flag(flag.to_string()),
+            Config::single(section, key, Value::String(value.to_string())),
+        ));
+        self.merge()
+    }
+
+    /// Change a value in the user file and save it
+    fn set_user_value(&mut self, path: &[&str], value: Value) -> Result<()> {
+        self.update_user_file(|table| Config::insert(table, path, value.clone()))
+    }
+
+    /// Remove a value from the user file and save it
+    fn remove_user_value(&mut self, path: &[&str]) -> Result<()> {
+        self.update_user_file(|table| Config::remove(table, path))
+    }
+
+    fn remove(table: &mut Table, path: &[&str]) {
+        let Some((key, parents)) = path.split_last() else {
+            return;
+        };
+        let mut table = table;
+        for parent in parents {
+            let Some(Value::Table(nested)) = table.get_mut(*parent) else {
+                return;
+            };
+            table = nested;
+        }
+        table.remove(*key);
+    }
+
+    /// Apply a change to the user file and save it. The change applies to this session even when
+    /// saving fails, e.g. on a read-only home directory, so callers only need to report the error.
+    /// A change that would make the config invalid is neither applied nor saved.
+    fn update_user_file(&mut self, change: impl Fn(&mut Table)) -> Result<()> {
+        let previous = self.layers[self.user_layer].1.clone();
+        change(&mut self.layers[self.user_layer].1);
+        if let Err(e) = self.merge() {
+            self.layers[self.user_layer].1 = previous;
+            self.merge()?;
+            return Err(e);
+        }
+        let Some(path) = self.path.clone() else {
+            return Ok(());
         };
-        Ok(home_dir.join(".sm_connect"))
+        let saved = Config::save_user_file(&path, &change)
+            .with_context(|| format!("failed to save {}", path.to_string_lossy()))?;
+        // Picks up what other instances saved meanwhile
+        self.layers[self.user_layer].1 = saved;
+        self.merge()
+            .with_context(|| format!("saved {}", path.to_string_lossy()))
+    }
+
+    /// Apply a change to the user file as it is on disk, so changes saved meanwhile by another
+    /// sm_connect are kept rather than overwritten. The file is locked while it is read and
+    /// written, and replaced in one step, so a crash never leaves it half written.
+    fn save_user_file(path: &Path, change: impl Fn(&mut Table)) -> Result<Table> {
+        // The file a symlinked config points to is replaced, rather than the link
+        let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut lock_path = path.as_os_str().to_owned();
+        lock_path.push(".lock");
+        let lock = File::create(&lock_path)?;
+        lock.lock()?;
+
+        // A file that doesn't parse, e.g. one being edited, is left alone rather than replaced
+        let mut table = Config::read_toml_file(&path)?.unwrap_or_default();
+        change(&mut table);
+        write_atomically(&path, &toml::to_string_pretty(&table)?)?;
+        Ok(table)
+    }
+
+    /// Every effective value as `section.key = value` lines, optionally with where it came from
+    pub fn show(&self, with_origin: bool) -> String {
+        let lines: Vec<(String, String)> = Config::leaves(&self.merged, &[])
+            .into_iter()
+            .map(|(path, value)| (Config::format_path(&path), value.to_string()))
+            .collect();
+        let width = lines
+            .iter()
+            .map(|(key, value)| key.len() + value.len())
+            .max()
+            .unwrap_or_default();
+        lines
+            .iter()
+            .map(|(key, value)| match self.origins.get(key) {
+                Some(origin) if with_origin => format!(
+                    "{} = {}{}  # {}\n",
+                    key,
+                    value,
+                    " ".repeat(width - key.len() - value.len()),
+                    origin
+                ),
+                _ => format!("{} = {}\n", key, value),
+            })
+            .collect()
+    }
+
+    /// `$XDG_CONFIG_HOME/sm_connect/config.toml`
+    fn get_config_path() -> Result<PathBuf> {
+        Ok(get_config_dir()?.join("config.toml"))
+    }
+
+    fn get_legacy_config_path() -> Option<PathBuf> {
+        home_dir().map(|home_dir| home_dir.join(".sm_connect"))
+    }
+
+    /// The closest `.sm_connect.toml` in the current directory or its parents
+    fn find_project_config() -> Option<PathBuf> {
+        std::env::current_dir()
+            .ok()?
+            .ancestors()
+            .map(|dir| dir.join(PROJECT_CONFIG_NAME))
+            .find(|path| path.is_file())
     }
 
     fn get_default_regions() -> Vec<String> {
         DEFAULT_REGIONS.iter().map(|s| s.to_string()).collect()
     }
 
+    fn to_list(items: &[String]) -> Value {
+        Value::Array(items.iter().cloned().map(Value::String).collect())
+    }
+
     pub fn get_visible_regions(&self) -> Vec<String> {
-        let Some(hidden_regions) = self.internal.get("regions", "hidden") else {
-            return Self::get_default_regions();
-        };
-        let hidden_regions: Vec<String> =
-            hidden_regions.split(",").map(|s| s.to_string()).collect();
+        let hidden_regions = &self.settings.regions.hidden;
         Self::get_default_regions()
             .into_iter()
             .filter(|r| !hidden_regions.contains(r))
             .collect()
     }
 
-    pub fn hide_region(&mut self, region: String) {
-        match self.internal.get("regions", "hidden") {
-            Some(hidden_regions) => {
-                self.internal.set(
-                    "regions",
-                    "hidden",
-                    format!("{},{}", hidden_regions, region).into(),
-                );
-            }
-            None => {
-                self.internal.set("regions", "hidden", region.into());
+    /// Hide a region in the user file. Regions are hidden from the regions the user file lists,
+    /// so hidden regions set by other layers aren't copied into it.
+    pub fn hide_region(&mut self, region: String) -> Result<()> {
+        self.update_user_regions("hidden", |hidden_regions| {
+            if !hidden_regions.contains(&region) {
+                hidden_regions.push(region.clone());
             }
-        }
-        self.persist();
+        })
     }
 
-    pub fn reset_hidden_regions(&mut self) {
-        self.internal
-            .set("regions", "hidden", "".to_string().into());
-        self.persist();
+    pub fn reset_hidden_regions(&mut self) -> Result<()> {
+        self.update_user_regions("hidden", Vec::clear)
     }
 
     pub fn get_favorite_regions(&self) -> Vec<String> {
-        let Some(visible_regions) = self.internal.get("regions", "favorite") else {
-            return Vec::new();
-        };
-        visible_regions.split(",").map(|s| s.to_string()).collect()
+        self.settings.regions.favorite.clone()
     }
 
-    pub fn toggle_favorite_region(&mut self, region: String) {
+    pub fn toggle_favorite_region(&mut self, region: String) -> Result<()> {
         let favorite_regions = self.get_favorite_regions();
         if favorite_regions.contains(&region) {
             self.unset_favorite_region(region)
         } else {
-            self.favorite_region(region);
+            self.favorite_region(region)
         }
-        self.persist();
     }
 
-    pub fn unset_favorite_region(&mut self, region: String) {
-        let favorite_regions = self.get_favorite_regions();
-        if favorite_regions.contains(&region) {
-            let new_favs: Vec<String> = favorite_regions
-                .iter()
-                .filter(|r| r != &&region)
-                .map(|s| s.to_string())
-                .collect();
-            self.internal
-                .set("regions", "favorite", new_favs.join(",").to_string().into());
-        }
-    }
-
-    pub fn favorite_region(&mut self, region: String) {
-        match self.internal.get("regions", "favorite") {
-            Some(favorite_regions) => {
-                self.internal.set(
-                    "regions",
-                    "favorite",
-                    format!("{},{}", favorite_regions, region).into(),
-                );
-            }
-            None => {
-                self.internal.set("regions", "favorite", region.into());
+    pub fn unset_favorite_region(&mut self, region: String) -> Result<()> {
+        self.update_user_regions("favorite", |favorite_regions| {
+            favorite_regions.retain(|r| r != &region)
+        })
+    }
+
+    pub fn favorite_region(&mut self, region: String) -> Result<()> {
+        self.update_user_regions("favorite", |favorite_regions| {
+            if !favorite_regions.contains(&region) {
+                favorite_regions.push(region.clone());
             }
+        })
+    }
+
+    /// Change a region list of the user file. The list is read from the file under the lock, so
+    /// regions another sm_connect added or removed meanwhile are kept.
+    fn update_user_regions(&mut self, key: &str, change: impl Fn(&mut Vec<String>)) -> Result<()> {
+        self.check_user_regions(key)?;
+        self.update_user_file(|table| {
+            let mut regions = Config::get_region_list(table, key);
+            change(&mut regions);
+            Config::insert(table, &["regions", key], Config::to_list(&regions));
+        })
+    }
+
+    /// A region list of one layer, empty when the layer doesn't set it
+    fn get_region_list(table: &Table, key: &str) -> Vec<String> {
+        table
+            .get("regions")
+            .and_then(|regions| regions.get(key))
+            .cloned()
+            .and_then(|regions| string_or_list(regions).ok())
+            .unwrap_or_default()
+    }
+
+    /// Changes are saved to the user file, so a region list set in a layer above it, e.g. the
+    /// project file or an environment variable, can't be changed from the UI
+    fn check_user_regions(&self, key: &str) -> Result<()> {
+        let path = format!("regions.{}", key);
+        let Some(origin) = self.origins.get(&path) else {
+            return Ok(());
+        };
+        let layer = self.layers.iter().rposition(|(layer, _)| layer == origin);
+        if layer.is_some_and(|layer| layer > self.user_layer) {
+            bail!("{} is set in {} and can't be changed here", path, origin);
         }
+        Ok(())
+    }
+
+    /// Default server-side filters for a fetch: `[filters] default` applies everywhere,
+    /// `[profiles.<name>] filters` and `[regions.filters] <name>` narrow it down.
+    pub fn get_instance_filters(&self, region: &str, profile: &str) -> Vec<String> {
+        let settings = &self.settings;
+        let profile_filters = settings
+            .profiles
+            .get(profile)
+            .map(|profile| profile.filters.clone())
+            .unwrap_or_default();
+        let region_filters = settings
+            .regions
+            .filters
+            .get(region)
+            .cloned()
+            .unwrap_or_default();
+        [&settings.filters.default, &profile_filters, &region_filters]
+            .into_iter()
+            .flatten()
+            .flat_map(|filters| split_filters(filters))
+            .collect()
+    }
+
+    /// Columns of the instances table for a profile
+    pub fn get_columns(&self, profile: &str) -> Vec<Column> {
+        match self.settings.profiles.get(profile) {
+            Some(section) if !section.columns.is_empty() => section.columns.clone(),
+            _ => self.settings.columns.default.clone(),
+        }
+    }
+
+    /// Save the columns of the instances table for a profile
+    pub fn set_columns(&mut self, profile: &str, columns: &[Column]) -> Result<()> {
+        let value = Value::try_from(columns)?;
+        self.set_user_value(&["profiles", profile, "columns"], value)
+    }
+
+    /// How long a cached inventory is shown without refreshing it, from `[cache] ttl` in seconds
+    pub fn get_cache_ttl(&self) -> Duration {
+        Duration::from_secs(self.settings.cache.ttl)
+    }
+
+    /// Region to open, skipping the region selection, from `[aws] region` or `--region`
+    pub fn get_region(&self) -> Option<String> {
+        self.settings
+            .aws
+            .region
+            .clone()
+            .filter(|region| !region.is_empty())
+    }
+
+    /// Saved connections from the `[bookmarks]` section, by alias
+    pub fn get_bookmarks(&self) -> Vec<(String, Bookmark)> {
+        self.settings
+            .bookmarks
+            .iter()
+            .map(|(alias, bookmark)| (alias.clone(), bookmark.clone()))
+            .collect()
+    }
+
+    pub fn get_bookmark(&self, alias: &str) -> Option<Bookmark> {
+        self.settings.bookmarks.get(alias).cloned()
+    }
+
+    /// Aliases are used as `@alias` in place of a query, so they must be a single word
+    pub fn add_bookmark(&mut self, alias: &str, bookmark: &Bookmark) -> Result<()> {
+        if alias.is_empty() || alias.starts_with('@') || alias.contains(char::is_whitespace) {
+            bail!(
+                "invalid alias '{}', expected a single word without a leading @",
+                alias
+            );
+        }
+        let value = Value::try_from(bookmark)?;
+        self.set_user_value(&["bookmarks", alias], value)
+    }
+
+    /// Remove a bookmark from the user file. One also defined in another file can't be removed
+    /// from here, and is reported rather than reappearing.
+    pub fn remove_bookmark(&mut self, alias: &str) -> Result<()> {
+        self.remove_user_value(&["bookmarks", alias])?;
+        let other = self.layers.iter().find(|(_, table)| {
+            table
+                .get("bookmarks")
+                .and_then(|bookmarks| bookmarks.get(alias))
+                .is_some()
+        });
+        if let Some((origin, _)) = other {
+            bail!(
+                "@{} is defined in {} and can't be removed here",
+                alias,
+                origin
+            );
+        }
+        Ok(())
+    }
+
+    /// A saved port forward from the `[tunnels]` section
+    pub fn get_tunnel(&self, name: &str) -> Option<Tunnel> {
+        self.settings.tunnels.get(name).cloned()
+    }
+
+    /// Keys bound to commands, by command name, on top of the default keybindings
+    pub fn get_keybindings(&self) -> BTreeMap<String, String> {
+        self.settings.keybindings.clone()
+    }
+
+    /// The `[theme]` section, naming the theme and defining palettes
+    pub fn get_theme(&self) -> ThemeSection {
+        self.settings.theme.clone()
+    }
+
+    /// Timeout and retry behavior for AWS calls, from the `[aws]` section
+    pub fn get_aws_settings(&self) -> AwsSettings {
+        let aws = &self.settings.aws;
+        AwsSettings {
+            profile: aws.profile.clone().filter(|profile| !profile.is_empty()),
+            max_attempts: aws.max_attempts.max(1),
+            adaptive_retries: aws.retry_mode == "adaptive",
+            connect_timeout: Duration::from_secs(aws.connect_timeout),
+            operation_timeout: Duration::from_secs(aws.operation_timeout),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEGACY_INI: &str = "[regions]
+hidden = us-east-1, eu-west-1,us-east-1
+favorite = eu-central-1
+
+[filters]
+default = instance-state-name=running tag:Env=prod
+profile.prod = tag:Team=data
+
+[cache]
+ttl = 60
+";
+
+    #[test]
+    fn migrate_ini_translates_lists_filters_and_numbers() {
+        let mut ini = Ini::new();
+        ini.read(LEGACY_INI.to_string()).unwrap();
+        let table = Config::migrate_ini(&ini);
+        let expected: Table = r#"
+            [regions]
+            hidden = ["us-east-1", "eu-west-1"]
+            favorite = ["eu-central-1"]
+
+            [filters]
+            default = ["instance-state-name=running", "tag:Env=prod"]
+
+            [profiles.prod]
+            filters = ["tag:Team=data"]
+
+            [cache]
+            ttl = 60
+        "#
+        .parse()
+        .unwrap();
+        assert_eq!(table, expected);
+    }
+
+    #[test]
+    fn legacy_ini_file_is_translated() {
+        let table = Config::parse_legacy_file(LEGACY_INI).unwrap();
+        assert_eq!(table["cache"]["ttl"].as_integer(), Some(60));
+    }
+
+    #[test]
+    fn legacy_toml_file_is_read_as_is() {
+        let table = Config::parse_legacy_file("[cache]\nttl = 60\n").unwrap();
+        assert_eq!(table, "[cache]\nttl = 60\n".parse::<Table>().unwrap());
+    }
+
+    #[test]
+    fn broken_toml_is_an_error_rather_than_migrated() {
+        let broken = "[regions\nhidden = [\"us-east-1\", \"eu-west-1\"]\n\n[theme]\nname = \"solarized\"\n\n[theme.palettes.solarized]\nhighlight_bg = \"#268bd2\"\n";
+        assert!(Config::parse_legacy_file(broken).is_err());
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sm_connect_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn ini_is_only_read_from_the_legacy_file() {
+        let dir = temp_dir("ini");
+        let path = dir.join("config.toml");
+        std::fs::write(&path, LEGACY_INI).unwrap();
+        let read = Config::read_user_file(&path);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert!(read.is_err());
+    }
+
+    #[test]
+    fn legacy_file_is_migrated_to_the_xdg_path_and_left_alone() {
+        let dir = temp_dir("legacy");
+        let legacy_path = dir.join(".sm_connect");
+        let path = dir.join("sm_connect").join("config.toml");
+        std::fs::write(&legacy_path, LEGACY_INI).unwrap();
+        Config::migrate_legacy_file(&legacy_path, &path).unwrap();
+        let migrated = Config::read_user_file(&path).unwrap();
+        let legacy = std::fs::read_to_string(&legacy_path).unwrap();
+
+        // Once migrated, the legacy file is ignored
+        std::fs::write(&legacy_path, "[cache]\nttl = 1\n").unwrap();
+        Config::migrate_legacy_file(&legacy_path, &path).unwrap();
+        let kept = Config::read_user_file(&path).unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(migrated["cache"]["ttl"].as_integer(), Some(60));
+        assert_eq!(legacy, LEGACY_INI);
+        assert_eq!(kept, migrated);
+    }
+
+    /// A config without a user file on disk, from the default layer and the given ones
+    fn layered(layers: &[(Origin, &str)], user_layer: usize) -> Config {
+        let mut config = Config::default();
+        config.layers.push((Origin::Default, Table::new()));
+        for (origin, table) in layers {
+            config.layers.push((origin.clone(), table.parse().unwrap()));
+        }
+        config.user_layer = user_layer;
+        config.merge().unwrap();
+        config
+    }
+
+    #[test]
+    fn region_changes_only_start_from_the_user_file() {
+        let mut config = layered(
+            &[
+                (
+                    Origin::File(PathBuf::from(SYSTEM_CONFIG_PATH)),
+                    "[regions]\nhidden = [\"af-south-1\"]\nfavorite = [\"eu-west-1\"]",
+                ),
+                (
+                    Origin::File(PathBuf::from("config.toml")),
+                    "[regions]\nhidden = [\"ap-east-1\"]",
+                ),
+            ],
+            2,
+        );
+        config.hide_region("us-east-1".to_string()).unwrap();
+        config.favorite_region("us-west-2".to_string()).unwrap();
+        let user_table = &config.layers[2].1;
+        assert_eq!(
+            Config::get_region_list(user_table, "hidden"),
+            ["ap-east-1", "us-east-1"]
+        );
+        assert_eq!(
+            Config::get_region_list(user_table, "favorite"),
+            ["us-west-2"]
+        );
+    }
+
+    #[test]
+    fn regions_set_above_the_user_file_cant_be_changed() {
+        let mut config = layered(
+            &[
+                (Origin::File(PathBuf::from("config.toml")), ""),
+                (
+                    Origin::Env("SM_CONNECT_REGIONS_HIDDEN".to_string()),
+                    "[regions]\nhidden = [\"af-south-1\"]",
+                ),
+            ],
+            1,
+        );
+        let error = config.reset_hidden_regions().unwrap_err();
+        assert!(error.to_string().contains("SM_CONNECT_REGIONS_HIDDEN"));
+        assert!(config.hide_region("us-east-1".to_string()).is_err());
+        assert!(Config::get_region_list(&config.layers[1].1, "hidden").is_empty());
+        assert_eq!(config.settings.regions.hidden, ["af-south-1"]);
+        config.favorite_region("us-east-1".to_string()).unwrap();
+        assert_eq!(config.get_favorite_regions(), ["us-east-1"]);
+    }
+
+    #[test]
+    fn regions_changed_meanwhile_in_the_user_file_are_kept() {
+        let path =
+            std::env::temp_dir().join(format!("sm_connect_test_{}.toml", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+        let mut config = layered(&[(Origin::File(path.clone()), "")], 1);
+        config.path = Some(path.clone());
+        std::fs::write(&path, "[regions]\nhidden = [\"af-south-1\"]\n").unwrap();
+        config.hide_region("us-east-1".to_string()).unwrap();
+        let saved = Config::read_toml_file(&path).unwrap().unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let _ = std::fs::remove_file(path.with_extension("toml.lock"));
+        assert_eq!(
+            Config::get_region_list(&saved, "hidden"),
+            ["af-south-1", "us-east-1"]
+        );
+        assert_eq!(config.settings.regions.hidden, ["af-south-1", "us-east-1"]);
+    }
+
+    #[test]
+    fn keys_with_dots_are_kept_whole() {
+        let mut config = layered(
+            &[(
+                Origin::File(PathBuf::from("config.toml")),
+                "[profiles.\"team.prod\"]\nfilters = [\"tag:Team=data\"]",
+            )],
+            1,
+        );
+        let bookmark = Bookmark {
+            region: "eu-west-1".to_string(),
+            profile: Some("team.prod".to_string()),
+            instance_id: Some("i-0123456789abcdef0".to_string()),
+            query: None,
+        };
+        config.add_bookmark("api.prod", &bookmark).unwrap();
+        assert!(config.add_bookmark("api prod", &bookmark).is_err());
+
+        let saved = config.get_bookmark("api.prod").unwrap();
+        assert_eq!(saved.region, "eu-west-1");
+        assert_eq!(saved.get_query(), "i-0123456789abcdef0");
+        assert!(config.get_bookmark("api").is_none());
+        assert_eq!(
+            config.get_instance_filters("eu-west-1", "team.prod"),
+            ["tag:Team=data"]
+        );
+        assert!(config
+            .show(false)
+            .contains("bookmarks.\"api.prod\" = { instance_id = \"i-0123456789abcdef0\""));
+    }
+
+    #[test]
+    fn invalid_changes_are_not_applied() {
+        let mut config = layered(&[(Origin::File(PathBuf::from("config.toml")), "")], 1);
+        let ttl = config.get_cache_ttl();
+        let change = config.set_user_value(&["cache", "ttl"], Value::String("soon".to_string()));
+        assert!(change.is_err());
+        assert!(config.layers[1].1.is_empty());
+        assert_eq!(config.get_cache_ttl(), ttl);
+    }
+
+    #[test]
+    fn env_values_are_strings_unless_typed_values_fit() {
+        let profile = Config::parse_env_value("aws", "profile", "123");
+        assert_eq!(profile["aws"]["profile"].as_str(), Some("123"));
+        let region = Config::parse_env_value("aws", "region", "eu-west-1");
+        assert_eq!(region["aws"]["region"].as_str(), Some("eu-west-1"));
+        let ttl = Config::parse_env_value("cache", "ttl", "60");
+        assert_eq!(ttl["cache"]["ttl"].as_integer(), Some(60));
+        let hidden = Config::parse_env_value("regions", "hidden", "[\"af-south-1\"]");
+        assert!(hidden["regions"]["hidden"].is_array());
+    }
+
+    #[test]
+    fn bookmarks_of_other_files_are_replaced_whole_and_not_removed() {
+        let mut config = layered(
+            &[
+                (
+                    Origin::File(PathBuf::from("config.toml")),
+                    "[bookmarks.db]\nregion = \"eu-west-1\"\ninstance_id = \"i-0123\"",
+                ),
+                (
+                    Origin::File(PathBuf::from(PROJECT_CONFIG_NAME)),
+                    "[bookmarks.db]\nregion = \"us-east-1\"\nquery = \"tag:Role=db\"",
+                ),
+            ],
+            1,
+        );
+        let bookmark = config.get_bookmark("db").unwrap();
+        assert_eq!(bookmark.region, "us-east-1");
+        assert_eq!(bookmark.instance_id, None);
+        assert_eq!(bookmark.get_query(), "tag:Role=db");
+
+        let error = config.remove_bookmark("db").unwrap_err();
+        assert!(error.to_string().contains(PROJECT_CONFIG_NAME));
+        assert!(config.get_bookmark("db").is_some());
     }
 }
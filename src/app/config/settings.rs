@@ -0,0 +1,348 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::aws::AwsSettings;
+
+const DEFAULT_CACHE_TTL_SECONDS: u64 = 300;
+
+/// Columns of the instances table unless configured otherwise
+pub const DEFAULT_COLUMNS: &[&str] = &["name", "id", "private_ip", "public_ip"];
+
+/// The typed config, as merged from every layer
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct Settings {
+    pub aws: AwsSection,
+    pub cache: CacheSection,
+    pub regions: RegionsSection,
+    pub filters: FiltersSection,
+    pub columns: ColumnsSection,
+    /// Settings for an AWS profile, by profile name
+    pub profiles: BTreeMap<String, ProfileSection>,
+    /// Saved connections, by alias
+    pub bookmarks: BTreeMap<String, Bookmark>,
+    /// Saved port forwards, by name
+    pub tunnels: BTreeMap<String, Tunnel>,
+    /// Keys bound to commands, by command name
+    pub keybindings: BTreeMap<String, String>,
+    pub theme: ThemeSection,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct AwsSection {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Region to open, skipping the region selection
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    pub max_attempts: u32,
+    /// `standard` or `adaptive`, adaptive also rate limits requests client side when throttled
+    pub retry_mode: String,
+    /// In seconds
+    pub connect_timeout: u64,
+    /// In seconds
+    pub operation_timeout: u64,
+}
+
+impl Default for AwsSection {
+    fn default() -> Self {
+        let defaults = AwsSettings::default();
+        AwsSection {
+            profile: None,
+            region: None,
+            max_attempts: defaults.max_attempts,
+            retry_mode: "standard".to_string(),
+            connect_timeout: defaults.connect_timeout.as_secs(),
+            operation_timeout: defaults.operation_timeout.as_secs(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct CacheSection {
+    /// How long a cached inventory is shown without refreshing it, in seconds
+    pub ttl: u64,
+}
+
+impl Default for CacheSection {
+    fn default() -> Self {
+        CacheSection {
+            ttl: DEFAULT_CACHE_TTL_SECONDS,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct RegionsSection {
+    #[serde(deserialize_with = "string_or_list")]
+    pub hidden: Vec<String>,
+    #[serde(deserialize_with = "string_or_list")]
+    pub favorite: Vec<String>,
+    /// Server-side filters for a region, by region name
+    pub filters: BTreeMap<String, Vec<String>>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct FiltersSection {
+    /// Server-side filters applied to every fetch
+    #[serde(deserialize_with = "string_or_list")]
+    pub default: Vec<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct ColumnsSection {
+    /// Columns of the instances table, for profiles without their own
+    #[serde(deserialize_with = "column_list")]
+    pub default: Vec<Column>,
+}
+
+impl Default for ColumnsSection {
+    fn default() -> Self {
+        ColumnsSection {
+            default: DEFAULT_COLUMNS
+                .iter()
+                .map(|field| Column::new(field))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ProfileSection {
+    #[serde(deserialize_with = "string_or_list")]
+    pub filters: Vec<String>,
+    /// Columns of the instances table, replacing `[columns] default`
+    #[serde(
+        deserialize_with = "column_list",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub columns: Vec<Column>,
+}
+
+/// A column of the instances table, written as the field name, or as
+/// `{ field = "tag:Role", width = 12 }` for a fixed width instead of fitting the content
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(from = "ColumnValue", into = "ColumnValue")]
+pub struct Column {
+    /// Any `list` field or `tag:<Key>`
+    pub field: String,
+    pub width: Option<u16>,
+}
+
+impl Column {
+    pub fn new(field: &str) -> Column {
+        Column {
+            field: field.to_string(),
+            width: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum ColumnValue {
+    Field(String),
+    Sized {
+        field: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        width: Option<u16>,
+    },
+}
+
+impl From<ColumnValue> for Column {
+    fn from(value: ColumnValue) -> Self {
+        match value {
+            ColumnValue::Field(field) => Column { field, width: None },
+            ColumnValue::Sized { field, width } => Column { field, width },
+        }
+    }
+}
+
+impl From<Column> for ColumnValue {
+    fn from(column: Column) -> Self {
+        match column.width {
+            None => ColumnValue::Field(column.field),
+            width => ColumnValue::Sized {
+                field: column.field,
+                width,
+            },
+        }
+    }
+}
+
+/// A saved connection to an instance id, or to whatever a query such as `tag:Role=web` matches
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Bookmark {
+    pub region: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub instance_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query: Option<String>,
+}
+
+impl Bookmark {
+    /// The query finding the bookmarked instance, as in `connect`
+    pub fn get_query(&self) -> String {
+        self.instance_id
+            .clone()
+            .or_else(|| self.query.clone())
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Tunnel {
+    /// Instance to forward through, as in `connect`
+    pub target: String,
+    pub remote_port: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub local_port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub remote_host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default)]
+pub struct ThemeSection {
+    /// `dark`, `light`, `high-contrast` or one of the palettes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// User defined palettes, by name
+    pub palettes: BTreeMap<String, Palette>,
+}
+
+/// Colors of a user defined theme, as names such as `lightgreen`, indexes or `#rrggbb`.
+/// Colors that aren't set come from the built-in theme it is based on.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Palette {
+    /// Built-in theme the palette starts from, `dark` unless set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base: Option<String>,
+    /// Help bar and tabs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active_tab: Option<String>,
+    /// Selected row of lists and tables
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight_fg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub highlight_bg: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Console output lines matching the search
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search_match: Option<String>,
+    /// Metrics charts of the info panel
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network_in: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network_out: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_check: Option<String>,
+}
+
+/// Lists can also be written as a comma separated string, which is what environment variables hold
+pub(super) fn string_or_list<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrList {
+        String(String),
+        List(Vec<String>),
+    }
+    Ok(match StringOrList::deserialize(deserializer)? {
+        StringOrList::String(list) => list
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect(),
+        StringOrList::List(list) => list,
+    })
+}
+
+/// Columns can also be written as a comma separated string of fields, as in `--fields`
+fn column_list<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Column>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrList {
+        String(String),
+        List(Vec<Column>),
+    }
+    Ok(match StringOrList::deserialize(deserializer)? {
+        StringOrList::String(list) => list
+            .split(',')
+            .map(str::trim)
+            .filter(|field| !field.is_empty())
+            .map(Column::new)
+            .collect(),
+        StringOrList::List(list) => list,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn columns(toml: &str) -> Vec<Column> {
+        toml::from_str::<ColumnsSection>(toml).unwrap().default
+    }
+
+    #[test]
+    fn columns_are_fields_or_sized_tables() {
+        assert_eq!(
+            columns(r#"default = ["name", { field = "tag:Role", width = 12 }, { field = "id" }]"#),
+            [
+                Column::new("name"),
+                Column {
+                    field: "tag:Role".to_string(),
+                    width: Some(12),
+                },
+                Column::new("id"),
+            ]
+        );
+        assert_eq!(
+            columns(r#"default = "name, tag:Role,,id""#),
+            [
+                Column::new("name"),
+                Column::new("tag:Role"),
+                Column::new("id")
+            ]
+        );
+        assert!(toml::from_str::<ColumnsSection>(r#"default = [{ width = 12 }]"#).is_err());
+    }
+
+    #[test]
+    fn columns_round_trip() {
+        let section = ColumnsSection {
+            default: vec![
+                Column::new("name"),
+                Column {
+                    field: "tag:Role".to_string(),
+                    width: Some(12),
+                },
+            ],
+        };
+        let toml = toml::to_string(&section).unwrap();
+        assert_eq!(
+            toml,
+            "default = [\"name\", { field = \"tag:Role\", width = 12 }]\n"
+        );
+        assert_eq!(columns(&toml), section.default);
+    }
+}
@@ -1,11 +1,45 @@
 use std::collections::HashMap;
+use std::fmt::Debug;
+use std::net::IpAddr;
+use std::sync::{Mutex, OnceLock, RwLock};
+use std::time::{Duration, SystemTime};
 
 use anyhow::Result;
+use aws_config::retry::RetryConfig;
+use aws_config::timeout::TimeoutConfig;
 use aws_config::{BehaviorVersion, Region};
+use aws_sdk_cloudwatch::{
+    primitives::DateTime,
+    types::{Dimension, Metric, MetricDataQuery, MetricStat, ScanBy},
+};
 use aws_sdk_ec2::{
+    error::{DisplayErrorContext, ProvideErrorMetadata, SdkError},
     types::{Filter, Instance},
     Client,
 };
+use base64::{engine::general_purpose::STANDARD, Engine};
+use thiserror::Error;
+
+use crate::cache::now;
+use crate::output::format_elapsed;
+
+/// Field names accepted by [`InstanceInfo::get_field`], besides `tag:<Key>`
+pub const FIELDS: &[&str] = &[
+    "name",
+    "id",
+    "private_ip",
+    "public_ip",
+    "image_id",
+    "type",
+    "launch_time",
+    "launch_age",
+    "vpc_id",
+    "subnet_id",
+    "az",
+    "state",
+    "region",
+    "security_groups",
+];
 
 #[derive(Debug, Clone)]
 pub struct InstanceInfo {
@@ -14,6 +48,13 @@ pub struct InstanceInfo {
 }
 
 impl InstanceInfo {
+    pub fn new(region: Region, raw_instance_data: Instance) -> Self {
+        InstanceInfo {
+            region,
+            raw_instance_data,
+        }
+    }
+
     pub fn get_name(&self) -> String {
         self.get_tags()
             .entry("Name".to_string())
@@ -64,7 +105,6 @@ impl InstanceInfo {
         self.raw_instance_data.image_id.clone().unwrap_or_default()
     }
 
-    #[allow(dead_code)]
     pub fn get_raw_instance_data(&self) -> Instance {
         self.raw_instance_data.clone()
     }
@@ -82,10 +122,89 @@ impl InstanceInfo {
             .map_or(String::default(), |x| x.to_string())
     }
 
+    /// Launch time as a Unix time, for sorting
+    pub fn get_launch_secs(&self) -> Option<i64> {
+        self.raw_instance_data
+            .launch_time
+            .map(|launch_time| launch_time.secs())
+    }
+
+    /// Time since the launch, e.g. `3d`
+    pub fn get_launch_age(&self) -> String {
+        self.raw_instance_data
+            .launch_time
+            .map_or(String::default(), |launch_time| {
+                let launched_at = u64::try_from(launch_time.secs()).unwrap_or_default();
+                format_elapsed(now().saturating_sub(launched_at))
+            })
+    }
+
     pub fn get_vpc_id(&self) -> String {
         self.raw_instance_data.vpc_id.clone().unwrap_or_default()
     }
 
+    pub fn get_subnet_id(&self) -> String {
+        self.raw_instance_data.subnet_id.clone().unwrap_or_default()
+    }
+
+    pub fn get_availability_zone(&self) -> String {
+        self.raw_instance_data
+            .placement
+            .as_ref()
+            .and_then(|placement| placement.availability_zone.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn get_state(&self) -> String {
+        self.raw_instance_data
+            .state
+            .as_ref()
+            .and_then(|state| state.name.as_ref())
+            .map_or(String::default(), |x| x.to_string())
+    }
+
+    /// Look up a field by name, as used in `--fields`: one of [`FIELDS`] or `tag:<Key>`.
+    /// Returns None for unknown fields.
+    pub fn get_field(&self, field: &str) -> Option<String> {
+        if let Some(key) = field.strip_prefix("tag:") {
+            return Some(self.get_tags().get(key).cloned().unwrap_or_default());
+        }
+        let value = match field {
+            "name" => self.get_name(),
+            "id" => self.get_instance_id(),
+            "private_ip" => self.get_private_ip(),
+            "public_ip" => self.get_public_ip(),
+            "image_id" => self.get_image_id(),
+            "type" => self.get_instance_type(),
+            "launch_time" => self.get_launch_time(),
+            "launch_age" => self.get_launch_age(),
+            "vpc_id" => self.get_vpc_id(),
+            "subnet_id" => self.get_subnet_id(),
+            "az" => self.get_availability_zone(),
+            "state" => self.get_state(),
+            "region" => self.get_region().to_string(),
+            "security_groups" => self.get_security_groups().join(","),
+            _ => return None,
+        };
+        Some(value)
+    }
+
+    /// Whether the instance matches a query, which is either `tag:Key=Value`, an IP address,
+    /// an exact instance id, or otherwise a case insensitive substring of the name
+    pub fn matches_query(&self, query: &str) -> bool {
+        if let Some((key, value)) = query.strip_prefix("tag:").and_then(|t| t.split_once('=')) {
+            return self.get_tags().get(key).is_some_and(|v| v == value);
+        }
+        if query.parse::<IpAddr>().is_ok() {
+            return self.get_private_ip() == query || self.get_public_ip() == query;
+        }
+        self.get_instance_id() == query
+            || self
+                .get_name()
+                .to_lowercase()
+                .contains(&query.to_lowercase())
+    }
+
     pub fn get_security_groups(&self) -> Vec<String> {
         let Some(ref security_groups) = self.raw_instance_data.security_groups else {
             return Vec::new();
@@ -98,33 +217,445 @@ impl InstanceInfo {
     }
 }
 
-pub async fn fetch_instances(region: Region) -> Result<Vec<InstanceInfo>> {
-    let config = aws_config::defaults(BehaviorVersion::latest())
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AwsErrorKind {
+    Throttling,
+    Auth,
+    AccessDenied,
+    Network,
+    Timeout,
+    Other,
+}
+
+impl AwsErrorKind {
+    pub fn get_title(&self) -> &'static str {
+        match self {
+            AwsErrorKind::Throttling => "Requests are being throttled",
+            AwsErrorKind::Auth => "Authentication failed",
+            AwsErrorKind::AccessDenied => "Access denied",
+            AwsErrorKind::Network => "Network error",
+            AwsErrorKind::Timeout => "Request timed out",
+            AwsErrorKind::Other => "AWS request failed",
+        }
+    }
+
+    pub fn get_hint(&self) -> &'static str {
+        match self {
+            AwsErrorKind::Throttling => "AWS is rate limiting this account, wait a moment and retry.",
+            AwsErrorKind::Auth => {
+                "Credentials are missing or expired. Log in again (e.g. `aws sso login`) and retry."
+            }
+            AwsErrorKind::AccessDenied => {
+                "The current credentials are not allowed to make this call, check the IAM policy."
+            }
+            AwsErrorKind::Network => "Could not reach AWS, check the network connection or proxy.",
+            AwsErrorKind::Timeout => {
+                "AWS did not answer in time. Retry, or raise the timeouts in the [aws] config section."
+            }
+            AwsErrorKind::Other => "Retry, or pick another region.",
+        }
+    }
+}
+
+/// An SDK error, classified so the UI can suggest what to do about it
+#[derive(Error, Debug)]
+#[error("{}: {message}", .kind.get_title())]
+pub struct AwsError {
+    pub kind: AwsErrorKind,
+    pub message: String,
+}
+
+impl AwsError {
+    fn from_sdk<E, R>(error: SdkError<E, R>) -> Self
+    where
+        E: ProvideErrorMetadata + std::error::Error + 'static,
+        R: Debug,
+    {
+        let message = DisplayErrorContext(&error).to_string();
+        let mentions_credentials = || {
+            let lowercase = message.to_lowercase();
+            lowercase.contains("credential") || lowercase.contains("token")
+        };
+        let kind = match &error {
+            SdkError::TimeoutError(_) => AwsErrorKind::Timeout,
+            SdkError::DispatchFailure(failure) if failure.is_timeout() => AwsErrorKind::Timeout,
+            SdkError::DispatchFailure(failure) if failure.is_io() => AwsErrorKind::Network,
+            // Credential provider failures surface while dispatching or building the request
+            SdkError::DispatchFailure(_) | SdkError::ConstructionFailure(_)
+                if mentions_credentials() =>
+            {
+                AwsErrorKind::Auth
+            }
+            SdkError::DispatchFailure(_) => AwsErrorKind::Network,
+            SdkError::ServiceError(service_error) => match service_error.err().code() {
+                Some(
+                    "Throttling"
+                    | "ThrottlingException"
+                    | "RequestLimitExceeded"
+                    | "TooManyRequestsException",
+                ) => AwsErrorKind::Throttling,
+                Some(
+                    "AuthFailure"
+                    | "ExpiredToken"
+                    | "ExpiredTokenException"
+                    | "InvalidClientTokenId"
+                    | "UnrecognizedClientException"
+                    | "RequestExpired",
+                ) => AwsErrorKind::Auth,
+                Some("UnauthorizedOperation" | "AccessDenied" | "AccessDeniedException") => {
+                    AwsErrorKind::AccessDenied
+                }
+                _ => AwsErrorKind::Other,
+            },
+            _ => AwsErrorKind::Other,
+        };
+        AwsError { kind, message }
+    }
+}
+
+/// Timeout and retry behavior for AWS calls
+#[derive(Debug, Clone)]
+pub struct AwsSettings {
+    /// Profile from the config or the command line, overriding `AWS_PROFILE`
+    pub profile: Option<String>,
+    pub max_attempts: u32,
+    pub adaptive_retries: bool,
+    pub connect_timeout: Duration,
+    pub operation_timeout: Duration,
+}
+
+impl Default for AwsSettings {
+    fn default() -> Self {
+        AwsSettings {
+            profile: None,
+            max_attempts: 3,
+            adaptive_retries: false,
+            connect_timeout: Duration::from_secs(5),
+            operation_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+static AWS_SETTINGS: OnceLock<AwsSettings> = OnceLock::new();
+
+/// Kept apart from the other settings, bookmarks can switch to another profile at runtime
+static PROFILE_OVERRIDE: RwLock<Option<String>> = RwLock::new(None);
+
+/// Set the timeout and retry behavior, must be called before the first AWS call
+pub fn set_aws_settings(settings: AwsSettings) {
+    set_profile(settings.profile.clone());
+    let _ = AWS_SETTINGS.set(settings);
+}
+
+/// Switch the profile used by the following AWS calls and sessions
+pub fn set_profile(profile: Option<String>) {
+    *PROFILE_OVERRIDE.write().unwrap() = profile;
+}
+
+async fn get_sdk_config(region: Region) -> aws_config::SdkConfig {
+    get_profile_sdk_config(profile_override(), region).await
+}
+
+/// SDK configs are cached per profile and region, resolving credentials and endpoints is slow
+async fn get_profile_sdk_config(profile: Option<String>, region: Region) -> aws_config::SdkConfig {
+    static SDK_CONFIGS: OnceLock<Mutex<HashMap<String, aws_config::SdkConfig>>> = OnceLock::new();
+    let configs = SDK_CONFIGS.get_or_init(|| Mutex::new(HashMap::new()));
+    let key = format!("{}/{}", profile.clone().unwrap_or_default(), region);
+    if let Some(config) = configs.lock().unwrap().get(&key) {
+        return config.clone();
+    }
+    let settings = AWS_SETTINGS.get_or_init(AwsSettings::default);
+    let retry_config = if settings.adaptive_retries {
+        RetryConfig::adaptive()
+    } else {
+        RetryConfig::standard()
+    };
+    let mut loader = aws_config::defaults(BehaviorVersion::latest());
+    if let Some(ref profile) = profile {
+        loader = loader.profile_name(profile);
+    }
+    let config = loader
         .region(region.clone())
+        .retry_config(retry_config.with_max_attempts(settings.max_attempts))
+        .timeout_config(
+            TimeoutConfig::builder()
+                .connect_timeout(settings.connect_timeout)
+                .operation_timeout(settings.operation_timeout)
+                .build(),
+        )
         .load()
         .await;
-    let client = Client::new(&config);
-    let filters = vec![Filter::builder()
+    configs.lock().unwrap().insert(key, config.clone());
+    config
+}
+
+async fn get_client(region: Region) -> Client {
+    Client::new(&get_sdk_config(region).await)
+}
+
+/// Name of the AWS profile credentials are loaded from, as the SDK resolves it
+pub fn current_profile() -> String {
+    profile_override()
+        .or_else(|| std::env::var("AWS_PROFILE").ok())
+        .unwrap_or_else(|| "default".to_string())
+}
+
+/// The profile explicitly selected with `--profile`, the config or a bookmark, if any
+pub fn profile_override() -> Option<String> {
+    PROFILE_OVERRIDE.read().unwrap().clone()
+}
+
+/// Split a list of filter expressions, separated by commas or whitespace. Quotes keep a value
+/// with spaces or commas in one expression, e.g. `tag:Team="Data Platform"`, and are removed.
+pub fn split_filters(filters: &str) -> Vec<String> {
+    let mut expressions = vec![];
+    let mut expression = String::new();
+    let mut quote = None;
+    for c in filters.chars() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => expression.push(c),
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == ',' || c.is_whitespace() => {
+                if !expression.is_empty() {
+                    expressions.push(std::mem::take(&mut expression));
+                }
+            }
+            None => expression.push(c),
+        }
+    }
+    if !expression.is_empty() {
+        expressions.push(expression);
+    }
+    expressions
+}
+
+/// Translate a filter expression into an EC2 filter.
+/// Supported forms are `tag:Key=Value`, `tag:Key` (instances having the tag) and
+/// `name=value` for any `DescribeInstances` filter, e.g. `instance-type=t3.micro`, `vpc-id=vpc-123`
+/// or `subnet-id=subnet-123`. Several values can be given separated by `|`.
+pub fn parse_filter(expression: &str) -> Result<Filter> {
+    let filter = match expression.split_once('=') {
+        Some((name, values)) if !name.is_empty() && !values.is_empty() => Filter::builder()
+            .name(name)
+            .set_values(Some(values.split('|').map(|v| v.to_string()).collect())),
+        None if expression.starts_with("tag:") && expression.len() > 4 => {
+            Filter::builder().name("tag-key").values(&expression[4..])
+        }
+        _ => {
+            return Err(anyhow::anyhow!(
+                "Invalid filter '{}', expected 'name=value' or 'tag:Key=Value'",
+                expression
+            ))
+        }
+    };
+    Ok(filter.build())
+}
+
+/// Build the EC2 filters for a fetch. Expressions for the same filter name override earlier ones,
+/// so more specific filters (region, ad-hoc) can be listed after the defaults. `tag:Key` filters
+/// all share the `tag-key` name and are kept together, instances must have every tag.
+fn build_filters(expressions: &[String]) -> Result<Vec<Filter>> {
+    let mut filters = vec![Filter::builder()
         .set_name(Some("instance-state-name".to_string()))
         .set_values(Some(vec!["running".to_string()]))
         .build()];
-    let result = client
+    for expression in expressions {
+        let filter = parse_filter(expression)?;
+        if filter.name() != Some("tag-key") {
+            filters.retain(|f| f.name != filter.name);
+        }
+        filters.push(filter);
+    }
+    Ok(filters)
+}
+
+pub async fn fetch_instances(region: Region, filters: &[String]) -> Result<Vec<InstanceInfo>> {
+    fetch_profile_instances(profile_override(), region, filters).await
+}
+
+/// Like `fetch_instances`, with a profile other than the current one
+pub async fn fetch_profile_instances(
+    profile: Option<String>,
+    region: Region,
+    filters: &[String],
+) -> Result<Vec<InstanceInfo>> {
+    let filters = build_filters(filters)?;
+    let client = Client::new(&get_profile_sdk_config(profile, region.clone()).await);
+    let reservations = client
         .describe_instances()
         .set_filters(Some(filters))
+        .into_paginator()
+        .items()
         .send()
-        .await?;
-
-    let binding = result.reservations.unwrap();
-    let instances: Vec<InstanceInfo> = binding
-        .iter()
-        .flat_map(|reservation| reservation.instances.clone().unwrap())
-        .map(|instance: Instance| {
-            let cloned = instance.clone();
-            InstanceInfo {
-                region: region.clone(),
-                raw_instance_data: cloned,
-            }
-        })
+        .collect::<Result<Vec<_>, _>>()
+        .await
+        .map_err(AwsError::from_sdk)?;
+
+    let instances: Vec<InstanceInfo> = reservations
+        .into_iter()
+        .flat_map(|reservation| reservation.instances.unwrap_or_default())
+        .map(|instance: Instance| InstanceInfo::new(region.clone(), instance))
         .collect();
     Ok(instances)
 }
+
+/// The identity the current credentials resolve to, as `arn (account id)`.
+/// STS answers in every region, `region` only selects the endpoint.
+pub async fn fetch_caller_identity(region: Region) -> Result<String> {
+    let client = aws_sdk_sts::Client::new(&get_sdk_config(region).await);
+    let identity = client
+        .get_caller_identity()
+        .send()
+        .await
+        .map_err(AwsError::from_sdk)?;
+    Ok(format!(
+        "{} (account {})",
+        identity.arn().unwrap_or_default(),
+        identity.account().unwrap_or_default()
+    ))
+}
+
+/// Fetch the serial console output of an instance.
+/// When `latest` is set, the most recent output is requested instead of the buffered one
+/// (only supported on Nitro instances).
+pub async fn fetch_console_output(instance: &InstanceInfo, latest: bool) -> Result<String> {
+    let client = get_client(instance.get_region()).await;
+    let result = client
+        .get_console_output()
+        .instance_id(instance.get_instance_id())
+        .latest(latest)
+        .send()
+        .await
+        .map_err(AwsError::from_sdk)?;
+
+    let Some(output) = result.output else {
+        return Ok(String::default());
+    };
+    let decoded = STANDARD.decode(output)?;
+    Ok(String::from_utf8_lossy(&decoded).to_string())
+}
+
+/// Fetch a screenshot of the instance console. AWS returns the image as a JPG.
+pub async fn fetch_console_screenshot(instance: &InstanceInfo) -> Result<Vec<u8>> {
+    let client = get_client(instance.get_region()).await;
+    let result = client
+        .get_console_screenshot()
+        .instance_id(instance.get_instance_id())
+        .wake_up(true)
+        .send()
+        .await
+        .map_err(AwsError::from_sdk)?;
+
+    let Some(image_data) = result.image_data else {
+        return Err(anyhow::anyhow!("No screenshot data returned"));
+    };
+    Ok(STANDARD.decode(image_data)?)
+}
+
+/// Datapoints for the last hour of the instance's basic CloudWatch metrics, oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct InstanceMetrics {
+    pub cpu_utilization: Vec<f64>,
+    pub network_in: Vec<f64>,
+    pub network_out: Vec<f64>,
+    pub status_check_failed: Vec<f64>,
+}
+
+const METRICS_PERIOD_SECONDS: i32 = 300;
+const METRICS_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+pub async fn fetch_instance_metrics(instance: &InstanceInfo) -> Result<InstanceMetrics> {
+    let client = aws_sdk_cloudwatch::Client::new(&get_sdk_config(instance.get_region()).await);
+    let instance_id = instance.get_instance_id();
+    let queries = [
+        ("cpu", "CPUUtilization", "Average"),
+        ("netin", "NetworkIn", "Sum"),
+        ("netout", "NetworkOut", "Sum"),
+        ("status", "StatusCheckFailed", "Maximum"),
+    ]
+    .iter()
+    .map(|(id, metric_name, stat)| {
+        let metric = Metric::builder()
+            .namespace("AWS/EC2")
+            .metric_name(*metric_name)
+            .dimensions(
+                Dimension::builder()
+                    .name("InstanceId")
+                    .value(&instance_id)
+                    .build(),
+            )
+            .build();
+        MetricDataQuery::builder()
+            .id(*id)
+            .metric_stat(
+                MetricStat::builder()
+                    .metric(metric)
+                    .period(METRICS_PERIOD_SECONDS)
+                    .stat(*stat)
+                    .build(),
+            )
+            .build()
+    })
+    .collect::<Vec<MetricDataQuery>>();
+
+    let now = SystemTime::now();
+    let result = client
+        .get_metric_data()
+        .set_metric_data_queries(Some(queries))
+        .start_time(DateTime::from(now - METRICS_WINDOW))
+        .end_time(DateTime::from(now))
+        .scan_by(ScanBy::TimestampAscending)
+        .send()
+        .await
+        .map_err(AwsError::from_sdk)?;
+
+    let mut metrics = InstanceMetrics::default();
+    for data in result.metric_data_results.unwrap_or_default() {
+        let values = data.values.unwrap_or_default();
+        match data.id.as_deref() {
+            Some("cpu") => metrics.cpu_utilization = values,
+            Some("netin") => metrics.network_in = values,
+            Some("netout") => metrics.network_out = values,
+            Some("status") => metrics.status_check_failed = values,
+            _ => {}
+        }
+    }
+    Ok(metrics)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_filters_keeps_quoted_values_together() {
+        assert_eq!(
+            split_filters("instance-type=t3.micro, tag:Team=\"Data Platform\" 'tag:Cost Center'"),
+            [
+                "instance-type=t3.micro",
+                "tag:Team=Data Platform",
+                "tag:Cost Center"
+            ]
+        );
+    }
+
+    #[test]
+    fn build_filters_keeps_every_tag_key_filter() {
+        let expressions = split_filters("tag:Env tag:Env=prod tag:Team tag:Env=staging");
+        let filters = build_filters(&expressions).unwrap();
+        let filters: Vec<(&str, Vec<String>)> = filters
+            .iter()
+            .map(|f| (f.name().unwrap(), f.values().to_vec()))
+            .collect();
+        assert_eq!(
+            filters,
+            [
+                ("instance-state-name", vec!["running".to_string()]),
+                ("tag-key", vec!["Env".to_string()]),
+                ("tag-key", vec!["Team".to_string()]),
+                ("tag:Env", vec!["staging".to_string()]),
+            ]
+        );
+    }
+}
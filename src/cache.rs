@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::Result;
+use aws_config::Region;
+use aws_sdk_ec2::primitives::DateTime;
+use aws_sdk_ec2::types::{
+    GroupIdentifier, Instance, InstanceState, InstanceStateName, InstanceType, Placement, Tag,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::aws::InstanceInfo;
+use crate::dirs::{get_cache_dir, write_atomically};
+
+/// Inventory of a region as last fetched, stored per profile and region
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedInventory {
+    fetched_at: u64,
+    filters: Vec<String>,
+    instances: Vec<CachedInstance>,
+}
+
+/// The subset of the EC2 instance data the app uses
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+struct CachedInstance {
+    instance_id: String,
+    tags: HashMap<String, String>,
+    private_ip: String,
+    public_ip: String,
+    image_id: String,
+    instance_type: String,
+    launch_time: Option<i64>,
+    vpc_id: String,
+    subnet_id: String,
+    availability_zone: String,
+    state: String,
+    security_groups: Vec<String>,
+}
+
+pub struct Inventory {
+    pub instances: Vec<InstanceInfo>,
+    pub age: Duration,
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+impl From<&InstanceInfo> for CachedInstance {
+    fn from(instance: &InstanceInfo) -> Self {
+        let raw = instance.get_raw_instance_data();
+        CachedInstance {
+            instance_id: instance.get_instance_id(),
+            tags: instance.get_tags(),
+            private_ip: instance.get_private_ip(),
+            public_ip: instance.get_public_ip(),
+            image_id: instance.get_image_id(),
+            instance_type: instance.get_instance_type(),
+            launch_time: raw.launch_time.map(|t| t.secs()),
+            vpc_id: instance.get_vpc_id(),
+            subnet_id: instance.get_subnet_id(),
+            availability_zone: instance.get_availability_zone(),
+            state: instance.get_state(),
+            security_groups: instance.get_security_groups(),
+        }
+    }
+}
+
+impl CachedInstance {
+    fn into_instance_info(self, region: Region) -> InstanceInfo {
+        let tags = self
+            .tags
+            .into_iter()
+            .map(|(key, value)| Tag::builder().key(key).value(value).build())
+            .collect();
+        let security_groups = self
+            .security_groups
+            .into_iter()
+            .map(|name| GroupIdentifier::builder().group_name(name).build())
+            .collect();
+        let instance = Instance::builder()
+            .set_instance_id(non_empty(&self.instance_id))
+            .set_tags(Some(tags))
+            .set_private_ip_address(non_empty(&self.private_ip))
+            .set_public_ip_address(non_empty(&self.public_ip))
+            .set_image_id(non_empty(&self.image_id))
+            .set_instance_type(
+                non_empty(&self.instance_type).map(|t| InstanceType::from(t.as_str())),
+            )
+            .set_launch_time(self.launch_time.map(DateTime::from_secs))
+            .set_vpc_id(non_empty(&self.vpc_id))
+            .set_subnet_id(non_empty(&self.subnet_id))
+            .placement(
+                Placement::builder()
+                    .set_availability_zone(non_empty(&self.availability_zone))
+                    .build(),
+            )
+            .set_state(non_empty(&self.state).map(|state| {
+                InstanceState::builder()
+                    .name(InstanceStateName::from(state.as_str()))
+                    .build()
+            }))
+            .set_security_groups(Some(security_groups))
+            .build();
+        InstanceInfo::new(region, instance)
+    }
+}
+
+/// Profile and region names are used as path components, so anything that could leave the
+/// cache directory, such as `/` or `..`, is escaped as `%XX`
+fn escape(component: &str) -> String {
+    component
+        .bytes()
+        .map(|b| {
+            if b.is_ascii_alphanumeric() || b"-_".contains(&b) {
+                (b as char).to_string()
+            } else {
+                format!("%{:02X}", b)
+            }
+        })
+        .collect()
+}
+
+fn get_profile_cache_dir(profile: &str) -> Result<PathBuf> {
+    Ok(get_cache_dir()?.join("inventory").join(escape(profile)))
+}
+
+fn get_cache_path(profile: &str, region: &str) -> Result<PathBuf> {
+    Ok(get_profile_cache_dir(profile)?.join(format!("{}.json", escape(region))))
+}
+
+fn read_inventory(profile: &str, region: &str) -> Option<CachedInventory> {
+    let path = get_cache_path(profile, region).ok()?;
+    let contents = std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&contents).ok()
+}
+
+/// Unix time, in seconds
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// Load the cached inventory for a profile and region.
+/// Inventories fetched with different filters are ignored, since they hold a different set of instances.
+pub fn load_inventory(profile: &str, region: &str, filters: &[String]) -> Option<Inventory> {
+    let inventory = read_inventory(profile, region)?;
+    if inventory.filters != filters {
+        return None;
+    }
+    let instances = inventory
+        .instances
+        .into_iter()
+        .map(|instance| instance.into_instance_info(Region::new(region.to_string())))
+        .collect();
+    Some(Inventory {
+        instances,
+        age: Duration::from_secs(now().saturating_sub(inventory.fetched_at)),
+    })
+}
+
+pub fn save_inventory(
+    profile: &str,
+    region: &str,
+    filters: &[String],
+    instances: &[InstanceInfo],
+) -> Result<()> {
+    let path = get_cache_path(profile, region)?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let inventory = CachedInventory {
+        fetched_at: now(),
+        filters: filters.to_vec(),
+        instances: instances.iter().map(CachedInstance::from).collect(),
+    };
+    // Another sm_connect may be reading it, e.g. for shell completions
+    write_atomically(&path, &serde_json::to_string(&inventory)?)?;
+    Ok(())
+}
+
+/// Every cached instance of a profile, in one region or in all of them, whatever filters were used.
+/// Meant for shell completions, which must never wait on the network.
+pub fn load_cached_instances(profile: &str, region: Option<&str>) -> Vec<InstanceInfo> {
+    let regions: Vec<String> = match region {
+        Some(region) => vec![region.to_string()],
+        None => get_profile_cache_dir(profile)
+            .and_then(|dir| Ok(std::fs::read_dir(dir)?))
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| {
+                        let file_name = entry.file_name().to_string_lossy().to_string();
+                        file_name.strip_suffix(".json").map(|s| s.to_string())
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+    };
+    regions
+        .iter()
+        .filter_map(|region| Some((region, read_inventory(profile, region)?)))
+        .flat_map(|(region, inventory)| {
+            inventory
+                .instances
+                .into_iter()
+                .map(|instance| instance.into_instance_info(Region::new(region.clone())))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_keeps_paths_inside_the_cache() {
+        assert_eq!(escape("prod-admin_1"), "prod-admin_1");
+        assert_eq!(escape("../etc"), "%2E%2E%2Fetc");
+        assert_eq!(escape("a\\b.c"), "a%5Cb%2Ec");
+    }
+}
@@ -0,0 +1,170 @@
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand};
+
+use crate::completions::CompletionShell;
+use crate::output::OutputFormat;
+use crate::tmux::TmuxLayout;
+
+/// Connect to EC2 instances through AWS Systems Manager Session Manager.
+/// Without a subcommand, the TUI is started to pick an instance to connect to.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Cli {
+    /// AWS profile to use, instead of the config, AWS_PROFILE or the default profile
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
+    /// AWS region, skips the region selection in the TUI. Overrides `[aws] region` in the config
+    #[arg(long, global = true)]
+    pub region: Option<String>,
+
+    /// Path to the config file, instead of ~/.config/sm_connect/config.toml
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    /// Print the command that starts the session, with its environment, instead of running it
+    #[arg(long, global = true, alias = "dry-run")]
+    pub print_command: bool,
+
+    /// Draw the TUI without colors, as when NO_COLOR is set
+    #[arg(long, global = true)]
+    pub no_color: bool,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    /// Colors are turned off by `--no-color`, or by a non-empty `NO_COLOR` as in https://no-color.org
+    pub fn colors_disabled(&self) -> bool {
+        self.no_color || std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty())
+    }
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum Command {
+    /// Start a shell session on an instance
+    Connect {
+        /// Instance to connect to: a name substring, an instance id, `tag:Key=Value`, an IP or a
+        /// bookmark as `@alias`. The TUI is opened, filtered by the query, unless exactly one
+        /// instance matches
+        query: Option<String>,
+        /// Inside tmux, how to open the instances marked with space in the TUI
+        #[arg(long, value_enum, default_value_t = TmuxLayout::Panes)]
+        tmux: TmuxLayout,
+        /// Inside tmux, send the input typed in one pane to every pane
+        #[arg(long)]
+        synchronize_panes: bool,
+    },
+    /// List the running instances of a region
+    List {
+        /// Output format
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+        /// Comma separated fields to print: name, id, private_ip, public_ip, image_id, type,
+        /// launch_time, vpc_id, subnet_id, az, state, region, security_groups or tag:<Key>
+        #[arg(
+            long,
+            value_delimiter = ',',
+            default_value = "name,id,private_ip,public_ip"
+        )]
+        fields: Vec<String>,
+    },
+    /// Forward a local port to a port on the instance, or on a host reachable from it
+    Forward {
+        /// Saved port forward from the `[tunnels]` config section, the other options override it
+        #[arg(long)]
+        tunnel: Option<String>,
+        /// Instance to forward through, as in `connect`. Picked in the TUI when not given
+        #[arg(long)]
+        target: Option<String>,
+        /// Port on the instance, or on the remote host
+        #[arg(long, required_unless_present = "tunnel")]
+        remote_port: Option<u16>,
+        /// Local port to listen on, defaults to the remote port
+        #[arg(long)]
+        local_port: Option<u16>,
+        /// Host to forward to through the instance, e.g. an RDS endpoint
+        #[arg(long)]
+        remote_host: Option<String>,
+    },
+    /// Run a command on an instance
+    Exec {
+        /// Instance to run the command on, as in `connect`. Picked in the TUI when not given
+        #[arg(long)]
+        target: Option<String>,
+        /// Command to run
+        #[arg(required = true, trailing_var_arg = true)]
+        command: Vec<String>,
+    },
+    /// Pick instances in the TUI and print them instead of connecting, e.g.
+    /// `ssh $(sm_connect pick --format '{private_ip}')`. Space marks several instances
+    Pick {
+        /// Line printed per instance, `{field}` is replaced with any field accepted by `list`
+        #[arg(long, default_value = "{id}")]
+        format: String,
+    },
+    /// List the visible regions, favorites first
+    Regions,
+    /// Generate ssh_config Host entries connecting to the instances of a region through Session Manager
+    SshConfig {
+        /// Update the managed section of the ssh config instead of printing it
+        #[arg(long)]
+        write: bool,
+        /// ssh config to update, defaults to ~/.ssh/config
+        #[arg(long)]
+        ssh_config: Option<PathBuf>,
+        /// Prefix for the Host aliases, e.g. `prod-`
+        #[arg(long, default_value = "")]
+        prefix: String,
+        /// User to log in as
+        #[arg(long)]
+        user: Option<String>,
+    },
+    /// Manage bookmarks, saved connections used as `@alias` in place of a query
+    Bookmark {
+        #[command(subcommand)]
+        action: BookmarkCommand,
+    },
+    /// Inspect the configuration
+    Config {
+        #[command(subcommand)]
+        action: ConfigCommand,
+    },
+    /// Check that the AWS CLI, the Session Manager plugin, credentials, the config and the terminal
+    /// are all usable, printing how to fix what isn't
+    Doctor,
+    /// Print the completion script for a shell, e.g. `source <(sm_connect completions bash)`
+    Completions {
+        #[arg(value_enum)]
+        shell: CompletionShell,
+    },
+    /// Print the cached instance names and ids, used by the completion scripts
+    #[command(hide = true)]
+    CompleteInstances,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum ConfigCommand {
+    /// Print the effective configuration, merged from every layer
+    Show {
+        /// Show where each value comes from: default, a file, an environment variable or a flag
+        #[arg(long)]
+        origin: bool,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+pub enum BookmarkCommand {
+    /// Bookmark an instance of the --region, with the current profile
+    Add {
+        alias: String,
+        /// Instance id, or a query as in `connect`, e.g. `tag:Role=web`
+        target: String,
+    },
+    /// List the bookmarks
+    List,
+    /// Remove a bookmark
+    Remove { alias: String },
+}
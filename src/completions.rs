@@ -0,0 +1,111 @@
+use std::io;
+
+use clap::{CommandFactory, ValueEnum};
+use clap_complete::{generate, shells};
+
+use crate::cache::load_cached_instances;
+use crate::cli::Cli;
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+// The generated scripts only know about the static CLI. These hooks complete instance names and ids
+// for `connect` and `--target` through the hidden `complete-instances` subcommand, forwarding the
+// --region, --profile and --config options already typed.
+
+const BASH_INSTANCES: &str = r#"
+_sm_connect_with_instances() {
+    local i cur prev args=()
+    cur="${COMP_WORDS[COMP_CWORD]}"
+    prev="${COMP_WORDS[COMP_CWORD-1]}"
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        case "${COMP_WORDS[i]}" in
+            --region|--profile|--config) args+=("${COMP_WORDS[i]}" "${COMP_WORDS[i+1]}") ;;
+        esac
+    done
+    if [[ "$prev" == "--target" ]] || [[ " ${COMP_WORDS[*]:1:COMP_CWORD-1} " == *" connect "* && "$cur" != -* && "$prev" != --* ]]; then
+        local IFS=$'\n'
+        COMPREPLY=($(compgen -W "$(sm_connect "${args[@]}" complete-instances 2>/dev/null)" -- "$cur"))
+        return 0
+    fi
+    _sm_connect "$@"
+}
+complete -F _sm_connect_with_instances -o bashdefault -o default sm_connect
+"#;
+
+const ZSH_INSTANCES: &str = r#"
+_sm_connect_with_instances() {
+    local i
+    local -a args instances
+    for ((i = 2; i < CURRENT; i++)); do
+        case "${words[i]}" in
+            --region|--profile|--config) args+=("${words[i]}" "${words[i+1]}") ;;
+        esac
+    done
+    if [[ "${words[CURRENT-1]}" == "--target" ]] || {
+        (( ${words[(I)connect]} > 0 && ${words[(I)connect]} < CURRENT )) &&
+            [[ "${words[CURRENT]}" != -* && "${words[CURRENT-1]}" != --* ]]
+    }; then
+        instances=(${(f)"$(sm_connect "${args[@]}" complete-instances 2>/dev/null)"})
+        compadd -a instances
+        return
+    fi
+    _sm_connect "$@"
+}
+compdef _sm_connect_with_instances sm_connect
+"#;
+
+const FISH_INSTANCES: &str = r#"
+function __sm_connect_instances
+    set -l tokens (commandline -opc)
+    set -l args
+    for i in (seq 2 (count $tokens))
+        switch $tokens[$i]
+            case --region --profile --config
+                set -a args $tokens[$i] $tokens[(math $i + 1)]
+        end
+    end
+    sm_connect $args complete-instances 2>/dev/null
+end
+complete -c sm_connect -n "__fish_seen_subcommand_from connect" -f -a "(__sm_connect_instances)"
+complete -c sm_connect -n "__fish_seen_subcommand_from forward exec" -l target -x -a "(__sm_connect_instances)"
+"#;
+
+pub fn print_completions(shell: CompletionShell) {
+    let mut command = Cli::command();
+    let name = command.get_name().to_string();
+    let mut stdout = io::stdout();
+    match shell {
+        CompletionShell::Bash => {
+            generate(shells::Bash, &mut command, name, &mut stdout);
+            print!("{}", BASH_INSTANCES);
+        }
+        CompletionShell::Zsh => {
+            generate(shells::Zsh, &mut command, name, &mut stdout);
+            print!("{}", ZSH_INSTANCES);
+        }
+        CompletionShell::Fish => {
+            generate(shells::Fish, &mut command, name, &mut stdout);
+            print!("{}", FISH_INSTANCES);
+        }
+    }
+}
+
+/// Instance names and ids from the cache, one per line
+pub fn instance_candidates(profile: &str, region: Option<&str>) -> String {
+    let mut candidates: Vec<String> = load_cached_instances(profile, region)
+        .iter()
+        .flat_map(|instance| [instance.get_name(), instance.get_instance_id()])
+        .filter(|candidate| !candidate.is_empty())
+        .collect();
+    candidates.sort();
+    candidates.dedup();
+    candidates
+        .iter()
+        .map(|candidate| format!("{}\n", candidate))
+        .collect()
+}
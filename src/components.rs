@@ -1,12 +1,21 @@
+pub mod bookmark_list;
+pub mod column_chooser;
+pub mod console_output;
+pub mod error_panel;
 pub mod instance_details;
 pub mod instance_table;
+pub mod keymap;
+pub mod recent_list;
 pub mod region_list;
 pub mod text_input;
+pub mod theme;
 use crossterm::event::{Event, KeyCode};
 
 use ratatui::{layout::Rect, widgets::Widget, Frame};
 
 use crate::aws::InstanceInfo;
+use crate::history::HistoryEntry;
+use crate::sort::SortOrder;
 
 pub enum Action {
     Noop,
@@ -14,6 +23,7 @@ pub enum Action {
     Return(String),
     ReturnWithKey(KeyCode),
     ReturnInstance(InstanceInfo),
+    ReturnInstances(Vec<InstanceInfo>),
     PartialReturn(String),
     Search,
     ToggleInfoPanel,
@@ -21,6 +31,18 @@ pub enum Action {
     Hide(String),
     Reset,
     ToggleFavorite(String),
+    ShowConsole(InstanceInfo),
+    Refresh,
+    Screenshot,
+    EditFilters,
+    ShowBookmarks,
+    Bookmark(InstanceInfo),
+    Remove(String),
+    ShowRecent,
+    ShowRegions,
+    Reconnect(HistoryEntry),
+    ChooseColumns,
+    Sort(SortOrder),
 }
 
 pub trait HandleAction {
@@ -0,0 +1,149 @@
+use crossterm::event::Event;
+use ratatui::{
+    layout::Rect,
+    widgets::{Block, Borders, List, ListItem, ListState},
+    Frame,
+};
+
+use super::keymap::{get_keymap, render_help, Command, Screen};
+use super::theme::get_theme;
+use super::{Action, HandleAction, Render, RenderHelp, View};
+use crate::app::config::Bookmark;
+
+#[derive(Default, Debug, Clone)]
+pub struct BookmarkList {
+    state: ListState,
+    items: Vec<(String, Bookmark)>,
+    status: String,
+}
+
+impl BookmarkList {
+    pub fn update_items(&mut self, items: Vec<(String, Bookmark)>) {
+        self.items = items;
+        match self.state.selected() {
+            _ if self.items.is_empty() => self.state.select(None),
+            Some(i) if i >= self.items.len() => self.state.select(Some(self.items.len() - 1)),
+            Some(_) => {}
+            None => self.state.select(Some(0)),
+        }
+    }
+
+    pub fn set_status(&mut self, status: String) {
+        self.status = status;
+    }
+
+    fn next(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) if i < self.items.len() - 1 => i + 1,
+            _ => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    fn previous(&mut self) {
+        if self.items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) if i > 0 => i - 1,
+            _ => self.items.len() - 1,
+        };
+        self.state.select(Some(i));
+    }
+
+    fn current(&self) -> Option<String> {
+        self.state
+            .selected()
+            .and_then(|i| self.items.get(i))
+            .map(|(alias, _)| alias.clone())
+    }
+}
+
+impl HandleAction for BookmarkList {
+    fn handle_action(&mut self, action: Event) -> Action {
+        match get_keymap().get_command(Screen::Bookmarks, &action) {
+            Some(Command::Back) => Action::Exit,
+            Some(Command::Down) => {
+                self.next();
+                Action::Noop
+            }
+            Some(Command::Up) => {
+                self.previous();
+                Action::Noop
+            }
+            Some(Command::Delete) => match self.current() {
+                Some(alias) => Action::Remove(alias),
+                None => Action::Noop,
+            },
+            Some(Command::Open) => match self.current() {
+                Some(alias) => Action::Return(alias),
+                None => Action::Noop,
+            },
+            _ => Action::Noop,
+        }
+    }
+}
+
+#[allow(refining_impl_trait)]
+impl View for BookmarkList {
+    fn get_widget(&self) -> List<'_> {
+        let width = self
+            .items
+            .iter()
+            .map(|(alias, _)| alias.chars().count())
+            .max()
+            .unwrap_or_default();
+        let items: Vec<ListItem> = self
+            .items
+            .iter()
+            .map(|(alias, bookmark)| {
+                ListItem::new(format!(
+                    "@{:width$}  {}  {}  {}",
+                    alias,
+                    bookmark.region,
+                    bookmark.profile.as_deref().unwrap_or("-"),
+                    bookmark.get_query(),
+                    width = width
+                ))
+            })
+            .collect();
+        let title = match get_keymap().get_key(Command::Bookmark) {
+            _ if !self.status.is_empty() => self.status.clone(),
+            _ if !self.items.is_empty() => "Bookmarks".to_string(),
+            Some(key) => format!(
+                "No bookmarks yet, press '{}' on an instance to add one",
+                key
+            ),
+            None => "No bookmarks yet".to_string(),
+        };
+
+        List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(get_theme().highlight())
+            .highlight_symbol(">> ")
+    }
+}
+
+impl Render for BookmarkList {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let widget = self.get_widget();
+        frame.render_stateful_widget(widget, area, &mut self.state.clone());
+    }
+}
+
+impl RenderHelp for BookmarkList {
+    fn render_help(&mut self, frame: &mut Frame, area: Rect) {
+        render_help(
+            frame,
+            area,
+            &[
+                (Command::Back, "Back"),
+                (Command::Open, "Connect"),
+                (Command::Delete, "Delete"),
+            ],
+        );
+    }
+}
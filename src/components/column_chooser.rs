@@ -0,0 +1,197 @@
+use crossterm::event::Event;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState},
+    Frame,
+};
+
+use super::keymap::{get_keymap, render_help, Command, Screen};
+use super::theme::get_theme;
+use super::{Action, HandleAction, Render, RenderHelp, View};
+use crate::app::config::Column;
+
+/// Dialog toggling and ordering the columns of the instances table.
+/// Shown columns are listed first, in table order, followed by the other fields.
+#[derive(Debug, Clone)]
+pub struct ColumnChooser {
+    state: ListState,
+    items: Vec<(Column, bool)>,
+}
+
+impl ColumnChooser {
+    pub fn new(columns: Vec<Column>, fields: Vec<String>) -> ColumnChooser {
+        let mut items: Vec<(Column, bool)> = columns
+            .iter()
+            .map(|column| (column.clone(), true))
+            .collect();
+        for field in fields {
+            if !columns.iter().any(|column| column.field == field) {
+                items.push((Column::new(&field), false));
+            }
+        }
+        let mut state = ListState::default();
+        state.select(Some(0));
+        ColumnChooser { state, items }
+    }
+
+    /// The checked columns, keeping their configured widths
+    pub fn get_columns(&self) -> Vec<Column> {
+        self.items
+            .iter()
+            .filter(|(_, shown)| *shown)
+            .map(|(column, _)| column.clone())
+            .collect()
+    }
+
+    fn toggle(&mut self) {
+        if let Some((_, shown)) = self.state.selected().and_then(|i| self.items.get_mut(i)) {
+            *shown = !*shown;
+        }
+    }
+
+    /// Move the selected column up or down the list, and so left or right in the table
+    fn move_selected(&mut self, down: bool) {
+        let Some(i) = self.state.selected() else {
+            return;
+        };
+        let other = if down { i + 1 } else { i.wrapping_sub(1) };
+        if other < self.items.len() {
+            self.items.swap(i, other);
+            self.state.select(Some(other));
+        }
+    }
+
+    fn next(&mut self) {
+        let i = match self.state.selected() {
+            Some(i) if i + 1 < self.items.len() => i + 1,
+            _ => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    fn previous(&mut self) {
+        let i = match self.state.selected() {
+            Some(i) if i > 0 => i - 1,
+            _ => self.items.len().saturating_sub(1),
+        };
+        self.state.select(Some(i));
+    }
+}
+
+impl HandleAction for ColumnChooser {
+    fn handle_action(&mut self, action: Event) -> Action {
+        match get_keymap().get_command(Screen::Columns, &action) {
+            Some(Command::Back) => Action::Exit,
+            Some(Command::Open) => Action::Return(String::default()),
+            Some(Command::Mark) => {
+                self.toggle();
+                Action::Noop
+            }
+            Some(Command::Down) => {
+                self.next();
+                Action::Noop
+            }
+            Some(Command::Up) => {
+                self.previous();
+                Action::Noop
+            }
+            Some(Command::MoveUp) => {
+                self.move_selected(false);
+                Action::Noop
+            }
+            Some(Command::MoveDown) => {
+                self.move_selected(true);
+                Action::Noop
+            }
+            _ => Action::Noop,
+        }
+    }
+}
+
+#[allow(refining_impl_trait)]
+impl View for ColumnChooser {
+    fn get_widget(&self) -> List<'_> {
+        let items: Vec<ListItem> = self
+            .items
+            .iter()
+            .map(|(column, shown)| {
+                let mark = if *shown { "[x]" } else { "[ ]" };
+                match column.width {
+                    Some(width) => ListItem::new(format!("{} {} ({})", mark, column.field, width)),
+                    None => ListItem::new(format!("{} {}", mark, column.field)),
+                }
+            })
+            .collect();
+
+        List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Columns"))
+            .highlight_style(get_theme().highlight())
+            .highlight_symbol(">> ")
+    }
+}
+
+impl Render for ColumnChooser {
+    /// Drawn as a dialog in the middle of `area`
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let vertical = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Percentage(15),
+                Constraint::Percentage(70),
+                Constraint::Percentage(15),
+            ])
+            .split(area);
+        let dialog = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Percentage(30),
+                Constraint::Percentage(40),
+                Constraint::Percentage(30),
+            ])
+            .split(vertical[1])[1];
+        frame.render_widget(Clear, dialog);
+        let widget = self.get_widget();
+        frame.render_stateful_widget(widget, dialog, &mut self.state.clone());
+    }
+}
+
+impl RenderHelp for ColumnChooser {
+    fn render_help(&mut self, frame: &mut Frame, area: Rect) {
+        render_help(
+            frame,
+            area,
+            &[
+                (Command::Mark, "Show/hide"),
+                (Command::MoveUp, "Move up"),
+                (Command::MoveDown, "Move down"),
+                (Command::Open, "Save for this profile"),
+                (Command::Back, "Close"),
+            ],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fields(columns: &[Column]) -> Vec<&str> {
+        columns.iter().map(|column| column.field.as_str()).collect()
+    }
+
+    #[test]
+    fn moving_a_column_changes_the_order() {
+        let columns = vec![Column::new("name"), Column::new("id")];
+        let mut chooser = ColumnChooser::new(columns, vec!["private_ip".to_string()]);
+        chooser.move_selected(false);
+        assert_eq!(fields(&chooser.get_columns()), ["name", "id"]);
+        chooser.move_selected(true);
+        assert_eq!(fields(&chooser.get_columns()), ["id", "name"]);
+
+        // An unchecked field moved among the shown ones appears there once checked
+        chooser.state.select(Some(2));
+        chooser.move_selected(false);
+        chooser.toggle();
+        assert_eq!(fields(&chooser.get_columns()), ["id", "private_ip", "name"]);
+    }
+}
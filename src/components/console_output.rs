@@ -0,0 +1,274 @@
+use crate::aws::InstanceInfo;
+use crossterm::event::Event;
+use ratatui::{
+    layout::Rect,
+    text::{Line, Text},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+
+use super::keymap::{get_keymap, render_help, Command, Screen};
+use super::text_input::TextInput;
+use super::theme::get_theme;
+use super::{Action, HandleAction, Render, RenderHelp, View};
+
+#[derive(Debug, Clone)]
+pub struct ConsoleOutput {
+    instance: Option<InstanceInfo>,
+    lines: Vec<String>,
+    scroll: usize,
+    page_size: usize,
+    search: String,
+    search_component: TextInput,
+    search_enabled: bool,
+    status: String,
+}
+
+impl Default for ConsoleOutput {
+    fn default() -> Self {
+        ConsoleOutput {
+            instance: None,
+            lines: Vec::new(),
+            scroll: 0,
+            page_size: 1,
+            search: String::default(),
+            search_component: TextInput::with_prompt("Find: "),
+            search_enabled: false,
+            status: String::default(),
+        }
+    }
+}
+
+impl ConsoleOutput {
+    pub fn set_instance(&mut self, instance: InstanceInfo) {
+        self.instance = Some(instance);
+        self.lines = Vec::new();
+        self.scroll = 0;
+        self.status = "Fetching console output...".into();
+    }
+
+    pub fn get_instance(&self) -> Option<InstanceInfo> {
+        self.instance.clone()
+    }
+
+    pub fn set_output(&mut self, output: String) {
+        self.lines = strip_escape_sequences(&output)
+            .lines()
+            .map(|line| line.to_string())
+            .collect();
+        if self.lines.is_empty() {
+            self.status = "No console output available yet".into();
+        } else {
+            self.status = String::default();
+        }
+        self.scroll_to_bottom();
+    }
+
+    pub fn set_status(&mut self, status: String) {
+        self.status = status;
+    }
+
+    fn max_scroll(&self) -> usize {
+        self.lines.len().saturating_sub(self.page_size)
+    }
+
+    fn scroll_down(&mut self, amount: usize) {
+        self.scroll = (self.scroll + amount).min(self.max_scroll());
+    }
+
+    fn scroll_up(&mut self, amount: usize) {
+        self.scroll = self.scroll.saturating_sub(amount);
+    }
+
+    fn scroll_to_bottom(&mut self) {
+        self.scroll = self.max_scroll();
+    }
+
+    /// Move the view to the next line matching the search, wrapping around at the end.
+    fn find_next(&mut self) {
+        if self.search.is_empty() || self.lines.is_empty() {
+            return;
+        }
+        let needle = self.search.to_lowercase();
+        let found = (1..=self.lines.len())
+            .map(|offset| (self.scroll + offset) % self.lines.len())
+            .find(|i| self.lines[*i].to_lowercase().contains(&needle));
+        match found {
+            Some(i) => {
+                self.scroll = i;
+                self.status = String::default();
+            }
+            None => self.status = format!("No match for '{}'", self.search),
+        }
+    }
+
+    fn save(&mut self) {
+        let Some(ref instance) = self.instance else {
+            return;
+        };
+        let file_name = format!("{}-console.log", instance.get_instance_id());
+        self.status = match std::fs::write(&file_name, self.lines.join("\n")) {
+            Ok(_) => format!("Saved to {}", file_name),
+            Err(e) => format!("Failed to save {}: {}", file_name, e),
+        };
+    }
+
+    fn handle_search_action(&mut self, action: Event) -> Action {
+        match self.search_component.handle_action(action) {
+            Action::Exit => {
+                self.search_enabled = false;
+            }
+            Action::Return(search) => {
+                self.search = search;
+                self.search_enabled = false;
+                self.find_next();
+            }
+            _ => {}
+        }
+        Action::Noop
+    }
+}
+
+impl HandleAction for ConsoleOutput {
+    fn handle_action(&mut self, action: Event) -> Action {
+        if self.search_enabled {
+            return self.handle_search_action(action);
+        }
+        match get_keymap().get_command(Screen::Console, &action) {
+            Some(Command::Back) => Action::Exit,
+            Some(Command::Down) => {
+                self.scroll_down(1);
+                Action::Noop
+            }
+            Some(Command::Up) => {
+                self.scroll_up(1);
+                Action::Noop
+            }
+            Some(Command::PageDown) => {
+                self.scroll_down(self.page_size);
+                Action::Noop
+            }
+            Some(Command::PageUp) => {
+                self.scroll_up(self.page_size);
+                Action::Noop
+            }
+            Some(Command::Top) => {
+                self.scroll = 0;
+                Action::Noop
+            }
+            Some(Command::Bottom) => {
+                self.scroll_to_bottom();
+                Action::Noop
+            }
+            Some(Command::Search) => {
+                self.search_enabled = true;
+                Action::Noop
+            }
+            Some(Command::NextMatch) => {
+                self.find_next();
+                Action::Noop
+            }
+            Some(Command::Save) => {
+                self.save();
+                Action::Noop
+            }
+            Some(Command::Refresh) => Action::Refresh,
+            Some(Command::Screenshot) => Action::Screenshot,
+            _ => Action::Noop,
+        }
+    }
+}
+
+#[allow(refining_impl_trait)]
+impl View for ConsoleOutput {
+    fn get_widget(&self) -> Paragraph<'_> {
+        let needle = self.search.to_lowercase();
+        let lines: Vec<Line> = self
+            .lines
+            .iter()
+            .map(|line| {
+                if !needle.is_empty() && line.to_lowercase().contains(&needle) {
+                    Line::styled(line.as_str(), get_theme().search_match())
+                } else {
+                    Line::raw(line.as_str())
+                }
+            })
+            .collect();
+        let title = match &self.instance {
+            Some(instance) => format!(
+                "Console output: {} ({}/{})",
+                instance.get_instance_id(),
+                (self.scroll + self.page_size).min(self.lines.len()),
+                self.lines.len()
+            ),
+            None => "Console output".to_string(),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .title_bottom(self.status.as_str());
+        Paragraph::new(Text::from(lines))
+            .block(block)
+            .scroll((self.scroll as u16, 0))
+    }
+}
+
+impl Render for ConsoleOutput {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        // Keep the page size in sync with the area minus the borders
+        self.page_size = area.height.saturating_sub(2).max(1) as usize;
+        let widget = self.get_widget();
+        frame.render_widget(widget, area);
+    }
+}
+
+impl RenderHelp for ConsoleOutput {
+    fn render_help(&mut self, frame: &mut Frame, area: Rect) {
+        if self.search_enabled {
+            self.search_component.render(frame, area);
+            frame.set_cursor(
+                area.x + self.search_component.get_cursor_position() as u16,
+                area.y,
+            );
+            return;
+        }
+        render_help(
+            frame,
+            area,
+            &[
+                (Command::Back, "Back"),
+                (Command::Search, "Find"),
+                (Command::NextMatch, "Next"),
+                (Command::Refresh, "Fetch latest"),
+                (Command::Save, "Save to file"),
+                (Command::Screenshot, "Save screenshot"),
+            ],
+        );
+    }
+}
+
+/// Serial consoles are full of ANSI color codes and carriage returns, which would garble the pane.
+fn strip_escape_sequences(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\u{1b}' => {
+                if chars.peek() == Some(&'[') {
+                    chars.next();
+                    // CSI sequences end with a byte in the range '@'..='~'
+                    for c in chars.by_ref() {
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+            }
+            '\r' => {}
+            '\t' => output.push_str("    "),
+            c if c.is_control() && c != '\n' => {}
+            c => output.push(c),
+        }
+    }
+    output
+}
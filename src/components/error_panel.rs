@@ -0,0 +1,81 @@
+use crate::aws::{AwsError, AwsErrorKind};
+use crossterm::event::Event;
+use ratatui::{
+    layout::Rect,
+    text::{Line, Text},
+    widgets::{Block, Borders, Paragraph, Wrap},
+    Frame,
+};
+
+use super::keymap::{get_keymap, render_help, Command, Screen};
+use super::theme::get_theme;
+use super::{Action, HandleAction, Render, RenderHelp, View};
+
+#[derive(Debug, Clone)]
+pub struct ErrorPanel {
+    kind: AwsErrorKind,
+    message: String,
+    can_continue: bool,
+}
+
+impl ErrorPanel {
+    /// `can_continue` allows going back to the previously loaded data
+    pub fn new(error: &anyhow::Error, can_continue: bool) -> ErrorPanel {
+        let (kind, message) = match error.downcast_ref::<AwsError>() {
+            Some(aws_error) => (aws_error.kind, aws_error.message.clone()),
+            None => (AwsErrorKind::Other, format!("{:#}", error)),
+        };
+        ErrorPanel {
+            kind,
+            message,
+            can_continue,
+        }
+    }
+}
+
+impl HandleAction for ErrorPanel {
+    fn handle_action(&mut self, action: Event) -> Action {
+        match get_keymap().get_command(Screen::Error, &action) {
+            Some(Command::Back) => Action::Exit,
+            Some(Command::Retry) => Action::Refresh,
+            Some(Command::Continue) if self.can_continue => Action::Return(String::default()),
+            _ => Action::Noop,
+        }
+    }
+}
+
+#[allow(refining_impl_trait)]
+impl View for ErrorPanel {
+    fn get_widget(&self) -> Paragraph<'_> {
+        let text = Text::from(vec![
+            Line::styled(self.kind.get_title(), get_theme().error()),
+            Line::raw(""),
+            Line::raw(self.kind.get_hint()),
+            Line::raw(""),
+            Line::raw(self.message.as_str()),
+        ]);
+        Paragraph::new(text)
+            .wrap(Wrap { trim: false })
+            .block(Block::default().borders(Borders::ALL).title("Error"))
+    }
+}
+
+impl Render for ErrorPanel {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let widget = self.get_widget();
+        frame.render_widget(widget, area);
+    }
+}
+
+impl RenderHelp for ErrorPanel {
+    fn render_help(&mut self, frame: &mut Frame, area: Rect) {
+        let mut help = vec![
+            (Command::Retry, "Retry"),
+            (Command::Back, "Back to regions"),
+        ];
+        if self.can_continue {
+            help.push((Command::Continue, "Continue with cached instances"));
+        }
+        render_help(frame, area, &help);
+    }
+}
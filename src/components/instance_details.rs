@@ -1,25 +1,134 @@
-use crate::aws::InstanceInfo;
-use ratatui::layout::Rect;
+use crate::aws::{InstanceInfo, InstanceMetrics};
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+use ratatui::style::Style;
 use ratatui::text::Text;
-use ratatui::widgets::{Block, Borders, Paragraph};
+use ratatui::widgets::{Block, Borders, Paragraph, Sparkline};
 use ratatui::Frame;
 
+use super::theme::get_theme;
 use super::{Render, View};
 
+#[derive(Debug, Clone, Default)]
+enum MetricsState {
+    #[default]
+    NotLoaded,
+    Loading,
+    Loaded(InstanceMetrics),
+    Failed(String),
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct InstanceDetails {
     instance: Option<InstanceInfo>,
+    metrics: MetricsState,
 }
 
 impl InstanceDetails {
     pub fn set_instance(&mut self, instance: InstanceInfo) {
+        let same_instance = self
+            .instance
+            .as_ref()
+            .is_some_and(|current| current.get_instance_id() == instance.get_instance_id());
+        if !same_instance {
+            self.metrics = MetricsState::NotLoaded;
+        }
         self.instance = Some(instance);
     }
+
+    /// Returns the instance whose metrics should be fetched, marking them as loading.
+    /// Metrics are only fetched once per selected instance.
+    pub fn start_metrics_fetch(&mut self) -> Option<InstanceInfo> {
+        match self.metrics {
+            MetricsState::NotLoaded => {
+                let instance = self.instance.clone()?;
+                self.metrics = MetricsState::Loading;
+                Some(instance)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn set_metrics(&mut self, instance_id: String, metrics: anyhow::Result<InstanceMetrics>) {
+        let is_current = self
+            .instance
+            .as_ref()
+            .is_some_and(|instance| instance.get_instance_id() == instance_id);
+        if !is_current {
+            return;
+        }
+        self.metrics = match metrics {
+            Ok(metrics) => MetricsState::Loaded(metrics),
+            Err(e) => MetricsState::Failed(format!("{:#}", e)),
+        };
+    }
+
+    fn render_metrics(&self, frame: &mut Frame, area: Rect) {
+        let metrics = match &self.metrics {
+            MetricsState::Loaded(metrics) => metrics,
+            MetricsState::Failed(error) => {
+                let widget = Paragraph::new(format!("Failed to load metrics: {}", error))
+                    .block(Block::default().borders(Borders::ALL).title("Metrics"));
+                frame.render_widget(widget, area);
+                return;
+            }
+            _ => {
+                let widget = Paragraph::new("Loading metrics...")
+                    .block(Block::default().borders(Borders::ALL).title("Metrics"));
+                frame.render_widget(widget, area);
+                return;
+            }
+        };
+        let [cpu, network_in, network_out, status_check] = get_theme().charts();
+        let sparklines = [
+            ("CPU %", &metrics.cpu_utilization, cpu, Some(100)),
+            ("Network In (bytes)", &metrics.network_in, network_in, None),
+            (
+                "Network Out (bytes)",
+                &metrics.network_out,
+                network_out,
+                None,
+            ),
+            (
+                "Status check failed",
+                &metrics.status_check_failed,
+                status_check,
+                Some(1),
+            ),
+        ];
+        let areas = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Ratio(1, 4); 4])
+            .split(area);
+        for ((title, values, color, max), area) in sparklines.iter().zip(areas.iter()) {
+            let data: Vec<u64> = values.iter().map(|v| v.round() as u64).collect();
+            let title = match values.last() {
+                Some(last) => format!("{}: {}", title, format_metric(*last)),
+                None => format!("{}: no data", title),
+            };
+            let mut sparkline = Sparkline::default()
+                .block(Block::default().borders(Borders::ALL).title(title))
+                .data(&data)
+                .style(Style::default().fg(*color));
+            if let Some(max) = max {
+                sparkline = sparkline.max(*max);
+            }
+            frame.render_widget(sparkline, *area);
+        }
+    }
+}
+
+fn format_metric(value: f64) -> String {
+    match value {
+        v if v >= 1_000_000_000.0 => format!("{:.1}G", v / 1_000_000_000.0),
+        v if v >= 1_000_000.0 => format!("{:.1}M", v / 1_000_000.0),
+        v if v >= 1_000.0 => format!("{:.1}K", v / 1_000.0),
+        v => format!("{:.1}", v),
+    }
 }
 
 #[allow(refining_impl_trait)]
 impl View for InstanceDetails {
-    fn get_widget(&self) -> Paragraph {
+    fn get_widget(&self) -> Paragraph<'_> {
         let text = match &self.instance {
             Some(instance) => {
                 let data = vec![
@@ -52,7 +161,17 @@ impl View for InstanceDetails {
 
 impl Render for InstanceDetails {
     fn render(&mut self, frame: &mut Frame, area: Rect) {
+        if self.instance.is_none() {
+            let widget = self.get_widget();
+            frame.render_widget(widget, area);
+            return;
+        }
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(area);
         let widget = self.get_widget();
-        frame.render_widget(widget, area);
+        frame.render_widget(widget, layout[0]);
+        self.render_metrics(frame, layout[1]);
     }
 }
@@ -1,9 +1,13 @@
+use crate::app::config::{Column, DEFAULT_COLUMNS};
 use crate::aws::InstanceInfo;
-use crossterm::event::{Event, KeyCode};
+use crate::sort::SortOrder;
+use crossterm::event::Event;
 use ratatui::{
-    layout::{Constraint, Rect}, style::{Color, Modifier, Style, Stylize}, text::Span, widgets::{Block, Borders, Cell, Row, Table, TableState}, Frame
+    layout::{Constraint, Rect}, style::{Modifier, Style, Stylize}, widgets::{Block, Borders, Row, Table, TableState}, Frame
 };
 
+use super::keymap::{get_keymap, render_help, Command, Screen};
+use super::theme::get_theme;
 use super::{Action, HandleAction, Render, RenderHelp, View};
 
 #[derive(Debug, Clone)]
@@ -12,38 +16,196 @@ pub struct InstanceTable {
     items: Vec<InstanceInfo>,
     visible_items: Vec<InstanceInfo>,
     filter: String,
+    status: String,
+    multi_select: bool,
+    marked: Vec<String>,
+    columns: Vec<Column>,
+    sort: SortOrder,
+}
+
+/// Header of a column, the key of tag columns
+fn get_column_title(field: &str) -> String {
+    if let Some(key) = field.strip_prefix("tag:") {
+        return key.to_string();
+    }
+    let title = match field {
+        "name" => "Name",
+        "id" => "InstanceId",
+        "private_ip" => "Private IP",
+        "public_ip" => "Public IP",
+        "image_id" => "Image",
+        "type" => "Type",
+        "launch_time" => "Launch time",
+        "launch_age" => "Age",
+        "vpc_id" => "VPC",
+        "subnet_id" => "Subnet",
+        "az" => "AZ",
+        "state" => "State",
+        "region" => "Region",
+        "security_groups" => "Security groups",
+        other => other,
+    };
+    title.to_string()
 }
 
 impl InstanceTable {
     pub fn with_items(items: Vec<InstanceInfo>) -> InstanceTable {
         let mut state = TableState::default();
         state.select(Some(0));
-        InstanceTable {
+        let mut table = InstanceTable {
             state,
             items: items.clone(),
             visible_items: items.clone(),
             filter: String::default(),
-        }
+            status: String::default(),
+            multi_select: false,
+            marked: Vec::new(),
+            columns: DEFAULT_COLUMNS
+                .iter()
+                .map(|field| Column::new(field))
+                .collect(),
+            sort: SortOrder::default(),
+        };
+        table.sort_items();
+        table
+    }
+
+    pub fn set_columns(&mut self, columns: Vec<Column>) {
+        self.columns = columns;
+    }
+
+    pub fn get_columns(&self) -> Vec<Column> {
+        self.columns.clone()
     }
 
-    pub fn with_items_and_filter(items: Vec<InstanceInfo>, filter: String) -> InstanceTable {
+    /// Tag keys of the instances, for tag columns
+    pub fn get_tag_keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .items
+            .iter()
+            .flat_map(|instance| instance.get_tags().into_keys())
+            .collect();
+        keys.sort();
+        keys.dedup();
+        keys
+    }
+
+    pub fn with_items_and_filter(
+        items: Vec<InstanceInfo>,
+        filter: String,
+        sort: SortOrder,
+    ) -> InstanceTable {
         let mut table = InstanceTable::with_items(items);
+        table.set_sort(sort);
         table.apply_filter(filter);
         table
     }
 
+    /// Replace the instances, keeping the current filter and selected instance
+    pub fn update_items(&mut self, items: Vec<InstanceInfo>) {
+        self.items = items;
+        self.sort_items();
+    }
+
+    fn set_sort(&mut self, sort: SortOrder) {
+        self.sort = sort;
+        self.sort_items();
+    }
+
+    /// Sort the instances, keeping the current filter and selected instance
+    fn sort_items(&mut self) {
+        let selected = self.current().map(|instance| instance.get_instance_id());
+        let sort = self.sort.clone();
+        self.items.sort_by(|a, b| sort.compare(a, b));
+        self.apply_filter(self.filter.clone());
+        if let Some(selected) = selected {
+            if let Some(i) = self
+                .visible_items
+                .iter()
+                .position(|instance| instance.get_instance_id() == selected)
+            {
+                self.state.select(Some(i));
+            }
+        }
+    }
+
+    /// Sort by the column after the current sort column, ascending
+    fn sort_by_next_column(&mut self) -> Action {
+        let next = match self
+            .columns
+            .iter()
+            .position(|column| column.field == self.sort.field)
+        {
+            Some(i) => self.columns.get(i + 1).or(self.columns.first()),
+            None => self.columns.first(),
+        };
+        let Some(column) = next else {
+            return Action::Noop;
+        };
+        self.set_sort(SortOrder {
+            field: column.field.clone(),
+            descending: false,
+        });
+        Action::Sort(self.sort.clone())
+    }
+
+    fn reverse_sort(&mut self) -> Action {
+        self.set_sort(SortOrder {
+            field: self.sort.field.clone(),
+            descending: !self.sort.descending,
+        });
+        Action::Sort(self.sort.clone())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn set_status(&mut self, status: String) {
+        self.status = status;
+    }
+
+    /// Allow marking several instances with space, returned together on enter
+    pub fn set_multi_select(&mut self, multi_select: bool) {
+        self.multi_select = multi_select;
+    }
+
+    fn toggle_mark(&mut self) {
+        let Some(instance) = self.current() else {
+            return;
+        };
+        let id = instance.get_instance_id();
+        match self.marked.iter().position(|marked| marked == &id) {
+            Some(i) => {
+                self.marked.remove(i);
+            }
+            None => self.marked.push(id),
+        }
+        self.next();
+    }
+
+    /// Marked instances in table order, or the current one when nothing is marked
+    fn selected_instances(&self) -> Vec<InstanceInfo> {
+        let marked: Vec<InstanceInfo> = self
+            .items
+            .iter()
+            .filter(|instance| self.marked.contains(&instance.get_instance_id()))
+            .cloned()
+            .collect();
+        if marked.is_empty() {
+            self.current().into_iter().collect()
+        } else {
+            marked
+        }
+    }
+
     pub fn apply_filter(&mut self, filter: String) {
         self.filter = filter;
         self.state.select(Some(0));
         self.visible_items = self
             .items
             .iter()
-            .filter(|instance| {
-                instance
-                    .get_name()
-                    .to_lowercase()
-                    .contains(&self.filter.to_lowercase())
-            })
+            .filter(|instance| instance.matches_query(&self.filter))
             .cloned()
             .collect()
     }
@@ -76,21 +238,35 @@ impl InstanceTable {
         self.state.select(Some(i));
     }
 
+    /// The only visible instance, if exactly one matches the filter
+    pub fn single_visible(&self) -> Option<InstanceInfo> {
+        match self.visible_items.as_slice() {
+            [instance] => Some(instance.clone()),
+            _ => None,
+        }
+    }
+
     pub fn current(&self) -> Option<InstanceInfo> {
-        self.state.selected().map(|i| self.visible_items[i].clone())
+        self.state
+            .selected()
+            .and_then(|i| self.visible_items.get(i).cloned())
     }
 
-    fn perform_key_action(&mut self, action: Option<&str>) -> Action {
+    fn perform_key_action(&mut self, command: Command) -> Action {
         if !self.visible_items.is_empty() {
-            match action {
-                Some("nextItem") => self.next(),
-                Some("previousItem") => self.previous(),
-                Some("accessItem") => {
+            match command {
+                Command::Down => self.next(),
+                Command::Up => self.previous(),
+                Command::Open if self.multi_select => {
+                    return Action::ReturnInstances(self.selected_instances());
+                }
+                Command::Open => {
                     return match self.current() {
                         Some(item) => Action::ReturnInstance(item),
                         None => Action::Noop,
                     };
                 }
+                Command::Mark => self.toggle_mark(),
                 _ => {}
             }
             match self.current() {
@@ -105,16 +281,26 @@ impl InstanceTable {
 
 impl HandleAction for InstanceTable {
     fn handle_action(&mut self, action: Event) -> Action {
-        match action {
-            Event::Key(key) => match key.code {
-                KeyCode::Char('q') => Action::Exit,
-                KeyCode::Down => self.perform_key_action(Some("nextItem")),
-                KeyCode::Up => self.perform_key_action(Some("previousItem")),
-                KeyCode::Right | KeyCode::Enter => self.perform_key_action(Some("accessItem")),
-                KeyCode::Char('/') => Action::Search,
-                KeyCode::Char('i') => Action::ToggleInfoPanel,
-                _ => Action::Noop,
+        match get_keymap().get_command(Screen::Instances, &action) {
+            Some(Command::Back) => Action::Exit,
+            Some(command @ (Command::Down | Command::Up | Command::Open)) => {
+                self.perform_key_action(command)
+            }
+            Some(Command::Mark) if self.multi_select => self.perform_key_action(Command::Mark),
+            Some(Command::Search) => Action::Search,
+            Some(Command::InfoPanel) => Action::ToggleInfoPanel,
+            Some(Command::Refresh) => Action::Refresh,
+            Some(Command::Console) => match self.current() {
+                Some(item) => Action::ShowConsole(item),
+                None => Action::Noop,
             },
+            Some(Command::Bookmark) => match self.current() {
+                Some(item) => Action::Bookmark(item),
+                None => Action::Noop,
+            },
+            Some(Command::Columns) => Action::ChooseColumns,
+            Some(Command::Sort) => self.sort_by_next_column(),
+            Some(Command::SortOrder) => self.reverse_sort(),
             _ => Action::Noop,
         }
     }
@@ -122,40 +308,68 @@ impl HandleAction for InstanceTable {
 
 #[allow(refining_impl_trait)]
 impl View for InstanceTable {
-    fn get_widget(&self) -> Table {
-        let items: Vec<Row> = self
+    fn get_widget(&self) -> Table<'_> {
+        let titles: Vec<String> = self
+            .columns
+            .iter()
+            .map(|column| {
+                let title = get_column_title(&column.field);
+                match self.sort {
+                    SortOrder { ref field, descending } if *field == column.field => {
+                        format!("{} {}", title, if descending { "▼" } else { "▲" })
+                    }
+                    _ => title,
+                }
+            })
+            .collect();
+        let rows: Vec<Vec<String>> = self
             .visible_items
             .iter()
-            .cloned()
             .map(|i| {
-                Row::new(vec![
-                    Cell::from(i.get_name()),
-                    Cell::from(i.get_instance_id()),
-                    Cell::from(i.get_private_ip()),
-                    Cell::from(i.get_public_ip()),
-                ])
-                .style(Style::default())
-                .height(1)
+                let mut values: Vec<String> = self
+                    .columns
+                    .iter()
+                    .map(|column| i.get_field(&column.field).unwrap_or_default())
+                    .collect();
+                // Marks go in front of the first column, usually the name
+                if let (true, Some(first)) = (self.multi_select, values.first_mut()) {
+                    let mark = if self.marked.contains(&i.get_instance_id()) {
+                        "[x]"
+                    } else {
+                        "[ ]"
+                    };
+                    *first = format!("{} {}", mark, first);
+                }
+                values
             })
             .collect();
-        let widths = [
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-        ];
+        // Columns without a fixed width fit their content
+        let widths: Vec<Constraint> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(index, column)| match column.width {
+                Some(width) => Constraint::Length(width),
+                None => Constraint::Length(
+                    rows.iter()
+                        .map(|values| values[index].chars().count())
+                        .chain(std::iter::once(titles[index].chars().count()))
+                        .max()
+                        .unwrap_or_default() as u16,
+                ),
+            })
+            .collect();
+        let items: Vec<Row> = rows
+            .into_iter()
+            .map(|values| Row::new(values).style(Style::default()).height(1))
+            .collect();
         // Create a List from all list items and highlight the currently selected one
         Table::new(items, widths)
-            .block(Block::default().borders(Borders::ALL))
-            .highlight_style(
-                Style::default()
-                    .bg(Color::LightGreen)
-                    .add_modifier(Modifier::BOLD),
-            )
+            .block(Block::default().borders(Borders::ALL).title(self.status.as_str()))
+            .highlight_style(get_theme().highlight())
             .highlight_symbol(">> ")
             .header(
-                Row::new(vec!["Name", "InstanceId", "Private IP", "Public IP"])
-                    .style(Style::default().add_modifier(Modifier::BOLD).underlined()),
+                Row::new(titles).style(Style::default().add_modifier(Modifier::BOLD).underlined()),
             )
     }
 }
@@ -169,29 +383,20 @@ impl Render for InstanceTable {
 
 impl RenderHelp for InstanceTable {
     fn render_help(&mut self, frame: &mut Frame, area: Rect) {
-        let rows = vec![Row::new(vec![
-            Cell::from(Span::styled(
-                "'/' Search",
-                Style::default().fg(Color::White),
-            )),
-            Cell::from(Span::styled(
-                "'q' Exit",
-                Style::default().fg(Color::White),
-            )),
-            Cell::from(Span::styled(
-                "'i' Info Panel",
-                Style::default().fg(Color::White),
-            )),
-        ])];
-        let table = Table::new(
-            rows,
-            vec![
-                Constraint::Min(10),
-                Constraint::Min(10),
-                Constraint::Min(10),
-                Constraint::Min(10),
-            ],
-        );
-        frame.render_widget(table, area);
-    }
-}
\ No newline at end of file
+        let mut help = vec![
+            (Command::Search, "Search"),
+            (Command::Back, "Exit"),
+            (Command::InfoPanel, "Info Panel"),
+            (Command::Console, "Console output"),
+            (Command::Refresh, "Refresh"),
+            (Command::Bookmark, "Bookmark"),
+            (Command::Columns, "Columns"),
+            (Command::Sort, "Sort"),
+            (Command::SortOrder, "Reverse"),
+        ];
+        if self.multi_select {
+            help.push((Command::Mark, "Select"));
+        }
+        render_help(frame, area, &help);
+    }
+}
@@ -0,0 +1,535 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, bail, Result};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use ratatui::{
+    layout::{Constraint, Rect},
+    text::Span,
+    widgets::{Cell, Row, Table},
+    Frame,
+};
+
+use super::theme::get_theme;
+
+/// A screen of the app. Commands are looked up per screen, so a key can do different things on
+/// different screens.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Screen {
+    Recent,
+    Bookmarks,
+    Regions,
+    Instances,
+    Console,
+    Error,
+    Columns,
+}
+
+impl fmt::Display for Screen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Screen::Recent => "recent",
+            Screen::Bookmarks => "bookmarks",
+            Screen::Regions => "region",
+            Screen::Instances => "instances",
+            Screen::Console => "console output",
+            Screen::Error => "error",
+            Screen::Columns => "column chooser",
+        };
+        write!(f, "{} screen", name)
+    }
+}
+
+const SCREENS: &[Screen] = &[
+    Screen::Recent,
+    Screen::Bookmarks,
+    Screen::Regions,
+    Screen::Instances,
+    Screen::Console,
+    Screen::Error,
+    Screen::Columns,
+];
+
+/// Something a key can be bound to, named in the `[keybindings]` config section
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    Quit,
+    Back,
+    Up,
+    Down,
+    Open,
+    PageUp,
+    PageDown,
+    Top,
+    Bottom,
+    Search,
+    NextMatch,
+    Hide,
+    ResetRegions,
+    Favorite,
+    Filters,
+    Bookmarks,
+    Recent,
+    Regions,
+    Mark,
+    InfoPanel,
+    Refresh,
+    Console,
+    Bookmark,
+    Delete,
+    Save,
+    Screenshot,
+    Retry,
+    Continue,
+    Columns,
+    Sort,
+    SortOrder,
+    MoveUp,
+    MoveDown,
+}
+
+/// Every command with its config name, default keys and the screens it applies to
+const COMMANDS: &[(Command, &str, &[&str], &[Screen])] = &[
+    (
+        Command::Quit,
+        "quit",
+        &["q"],
+        &[Screen::Recent, Screen::Regions],
+    ),
+    (
+        Command::Back,
+        "back",
+        &["q", "esc", "left"],
+        &[
+            Screen::Bookmarks,
+            Screen::Instances,
+            Screen::Console,
+            Screen::Error,
+            Screen::Columns,
+        ],
+    ),
+    (
+        Command::Up,
+        "up",
+        &["up"],
+        &[
+            Screen::Recent,
+            Screen::Bookmarks,
+            Screen::Regions,
+            Screen::Instances,
+            Screen::Console,
+            Screen::Columns,
+        ],
+    ),
+    (
+        Command::Down,
+        "down",
+        &["down"],
+        &[
+            Screen::Recent,
+            Screen::Bookmarks,
+            Screen::Regions,
+            Screen::Instances,
+            Screen::Console,
+            Screen::Columns,
+        ],
+    ),
+    (
+        Command::Open,
+        "open",
+        &["enter", "right"],
+        &[
+            Screen::Recent,
+            Screen::Bookmarks,
+            Screen::Regions,
+            Screen::Instances,
+            Screen::Columns,
+        ],
+    ),
+    (Command::PageUp, "page_up", &["pageup"], &[Screen::Console]),
+    (
+        Command::PageDown,
+        "page_down",
+        &["pagedown"],
+        &[Screen::Console],
+    ),
+    (Command::Top, "top", &["g", "home"], &[Screen::Console]),
+    (Command::Bottom, "bottom", &["G", "end"], &[Screen::Console]),
+    (
+        Command::Search,
+        "search",
+        &["/"],
+        &[Screen::Recent, Screen::Instances, Screen::Console],
+    ),
+    (Command::NextMatch, "next_match", &["n"], &[Screen::Console]),
+    (Command::Hide, "hide", &["h"], &[Screen::Regions]),
+    (
+        Command::ResetRegions,
+        "reset_regions",
+        &["r"],
+        &[Screen::Regions],
+    ),
+    (Command::Favorite, "favorite", &["*"], &[Screen::Regions]),
+    (Command::Filters, "filters", &["f"], &[Screen::Regions]),
+    (Command::Bookmarks, "bookmarks", &["b"], &[Screen::Regions]),
+    (Command::Recent, "recent", &["l"], &[Screen::Regions]),
+    (
+        Command::Regions,
+        "regions",
+        &["esc", "left"],
+        &[Screen::Recent],
+    ),
+    (
+        Command::Mark,
+        "mark",
+        &["space"],
+        &[Screen::Instances, Screen::Columns],
+    ),
+    (
+        Command::InfoPanel,
+        "info_panel",
+        &["i"],
+        &[Screen::Instances],
+    ),
+    (
+        Command::Refresh,
+        "refresh",
+        &["r"],
+        &[Screen::Instances, Screen::Console],
+    ),
+    (Command::Console, "console", &["c"], &[Screen::Instances]),
+    (Command::Bookmark, "bookmark", &["b"], &[Screen::Instances]),
+    (Command::Delete, "delete", &["d"], &[Screen::Bookmarks]),
+    (Command::Save, "save", &["s"], &[Screen::Console]),
+    (
+        Command::Screenshot,
+        "screenshot",
+        &["p"],
+        &[Screen::Console],
+    ),
+    (Command::Retry, "retry", &["r", "enter"], &[Screen::Error]),
+    (Command::Continue, "continue", &["c"], &[Screen::Error]),
+    (Command::Columns, "columns", &["v"], &[Screen::Instances]),
+    (Command::Sort, "sort", &["s"], &[Screen::Instances]),
+    (
+        Command::SortOrder,
+        "sort_order",
+        &["S"],
+        &[Screen::Instances],
+    ),
+    (Command::MoveUp, "move_up", &["K"], &[Screen::Columns]),
+    (Command::MoveDown, "move_down", &["J"], &[Screen::Columns]),
+];
+
+/// Default keys of a command that differ on one screen, used unless the command is rebound
+const SCREEN_DEFAULTS: &[(Command, Screen, &[&str])] =
+    &[(Command::Back, Screen::Instances, &["q"])];
+
+impl Command {
+    fn get_name(&self) -> &'static str {
+        COMMANDS
+            .iter()
+            .find(|(command, ..)| command == self)
+            .map(|(_, name, ..)| *name)
+            .unwrap_or_default()
+    }
+}
+
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.get_name())
+    }
+}
+
+/// A key with the modifiers that matter, shift is part of the character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+const NAMED_KEYS: &[(&str, KeyCode)] = &[
+    ("enter", KeyCode::Enter),
+    ("esc", KeyCode::Esc),
+    ("space", KeyCode::Char(' ')),
+    ("tab", KeyCode::Tab),
+    ("backspace", KeyCode::Backspace),
+    ("delete", KeyCode::Delete),
+    ("insert", KeyCode::Insert),
+    ("left", KeyCode::Left),
+    ("right", KeyCode::Right),
+    ("up", KeyCode::Up),
+    ("down", KeyCode::Down),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("pageup", KeyCode::PageUp),
+    ("pagedown", KeyCode::PageDown),
+];
+
+impl Key {
+    /// Parse a key such as `q`, `G`, `*`, `enter`, `pagedown`, `f5`, `ctrl-r` or `alt-x`
+    fn parse(key: &str) -> Result<Key> {
+        let mut modifiers = KeyModifiers::NONE;
+        let mut rest = key;
+        loop {
+            if let Some(stripped) = rest.strip_prefix("ctrl-").filter(|s| !s.is_empty()) {
+                modifiers |= KeyModifiers::CONTROL;
+                rest = stripped;
+            } else if let Some(stripped) = rest.strip_prefix("alt-").filter(|s| !s.is_empty()) {
+                modifiers |= KeyModifiers::ALT;
+                rest = stripped;
+            } else {
+                break;
+            }
+        }
+        let mut chars = rest.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => {
+                let name = rest.to_lowercase();
+                match NAMED_KEYS.iter().find(|(named, _)| *named == name) {
+                    Some((_, code)) => *code,
+                    None => name
+                        .strip_prefix('f')
+                        .and_then(|number| number.parse::<u8>().ok())
+                        .filter(|number| (1..=12).contains(number))
+                        .map(KeyCode::F)
+                        .ok_or_else(|| anyhow!("unknown key '{}'", key))?,
+                }
+            }
+        };
+        Ok(Key { code, modifiers })
+    }
+
+    fn matches(&self, event: &KeyEvent) -> bool {
+        self.code == event.code
+            && self.modifiers == event.modifiers & (KeyModifiers::CONTROL | KeyModifiers::ALT)
+    }
+}
+
+impl fmt::Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            write!(f, "ctrl-")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            write!(f, "alt-")?;
+        }
+        match NAMED_KEYS.iter().find(|(_, code)| *code == self.code) {
+            Some((name, _)) => write!(f, "{}", name),
+            None => match self.code {
+                KeyCode::Char(c) => write!(f, "{}", c),
+                KeyCode::F(number) => write!(f, "f{}", number),
+                code => write!(f, "{:?}", code),
+            },
+        }
+    }
+}
+
+/// The keys bound to every command
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: Vec<(Command, Vec<Key>)>,
+    /// Keys of a command on one screen, in place of its keys on the others
+    screen_bindings: Vec<(Command, Screen, Vec<Key>)>,
+}
+
+impl Keymap {
+    /// The default bindings, with `overrides` on top. Overrides map command names to keys
+    /// separated by spaces, an empty string unbinds the command.
+    /// Fails on unknown commands or keys, and when a key is bound twice on the same screen.
+    pub fn new(overrides: &BTreeMap<String, String>) -> Result<Keymap> {
+        if let Some(name) = overrides
+            .keys()
+            .find(|name| !COMMANDS.iter().any(|(_, command, ..)| command == name))
+        {
+            let names: Vec<&str> = COMMANDS.iter().map(|(_, name, ..)| *name).collect();
+            bail!(
+                "unknown command '{}', expected one of {}",
+                name,
+                names.join(", ")
+            );
+        }
+        let mut bindings = Vec::new();
+        for (command, name, defaults, _) in COMMANDS {
+            let keys = match overrides.get(*name) {
+                Some(keys) => keys
+                    .split_whitespace()
+                    .map(Key::parse)
+                    .collect::<Result<Vec<Key>>>()
+                    .map_err(|e| anyhow!("{} for '{}'", e, name))?,
+                None => defaults
+                    .iter()
+                    .map(|key| Key::parse(key))
+                    .collect::<Result<Vec<Key>>>()?,
+            };
+            bindings.push((*command, keys));
+        }
+        let mut screen_bindings = Vec::new();
+        for (command, screen, defaults) in SCREEN_DEFAULTS {
+            if !overrides.contains_key(command.get_name()) {
+                let keys = defaults
+                    .iter()
+                    .map(|key| Key::parse(key))
+                    .collect::<Result<Vec<Key>>>()?;
+                screen_bindings.push((*command, *screen, keys));
+            }
+        }
+        let keymap = Keymap {
+            bindings,
+            screen_bindings,
+        };
+        keymap.check_conflicts()?;
+        Ok(keymap)
+    }
+
+    fn check_conflicts(&self) -> Result<()> {
+        for screen in SCREENS {
+            let mut bound: Vec<(Key, Command)> = Vec::new();
+            for (command, keys) in self.get_screen_bindings(*screen) {
+                for key in keys {
+                    if let Some((_, other)) = bound.iter().find(|(existing, _)| existing == key) {
+                        bail!(
+                            "'{}' is bound to both {} and {} on the {}",
+                            key,
+                            other,
+                            command,
+                            screen
+                        );
+                    }
+                    bound.push((*key, command));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn get_screen_bindings(&self, screen: Screen) -> impl Iterator<Item = (Command, &Vec<Key>)> {
+        self.bindings
+            .iter()
+            .filter(move |(command, _)| {
+                COMMANDS
+                    .iter()
+                    .any(|(other, _, _, screens)| other == command && screens.contains(&screen))
+            })
+            .map(move |(command, keys)| {
+                let keys = self
+                    .screen_bindings
+                    .iter()
+                    .find(|(other, other_screen, _)| other == command && *other_screen == screen)
+                    .map_or(keys, |(_, _, keys)| keys);
+                (*command, keys)
+            })
+    }
+
+    /// The command a key press triggers on a screen
+    pub fn get_command(&self, screen: Screen, event: &Event) -> Option<Command> {
+        let Event::Key(key_event) = event else {
+            return None;
+        };
+        self.get_screen_bindings(screen)
+            .find(|(_, keys)| keys.iter().any(|key| key.matches(key_event)))
+            .map(|(command, _)| command)
+    }
+
+    /// The first key bound to a command, as shown in the help
+    pub fn get_key(&self, command: Command) -> Option<Key> {
+        self.bindings
+            .iter()
+            .find(|(other, _)| *other == command)
+            .and_then(|(_, keys)| keys.first().copied())
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::new(&BTreeMap::new()).expect("the default keybindings conflict")
+    }
+}
+
+static KEYMAP: OnceLock<Keymap> = OnceLock::new();
+
+/// Set the keybindings, must be called before the UI starts
+pub fn set_keymap(keymap: Keymap) {
+    let _ = KEYMAP.set(keymap);
+}
+
+pub fn get_keymap() -> &'static Keymap {
+    KEYMAP.get_or_init(Keymap::default)
+}
+
+/// Render the help bar of a screen from the active keymap, a `'key' Label` cell per command.
+/// Unbound commands are left out.
+pub fn render_help(frame: &mut Frame, area: Rect, help: &[(Command, &str)]) {
+    let keymap = get_keymap();
+    let cells: Vec<Cell> = help
+        .iter()
+        .filter_map(|(command, label)| {
+            let key = keymap.get_key(*command)?;
+            Some(Cell::from(Span::styled(
+                format!("'{}' {}", key, label),
+                get_theme().text(),
+            )))
+        })
+        .collect();
+    let widths = vec![Constraint::Min(10); cells.len()];
+    let table = Table::new(vec![Row::new(cells)], widths);
+    frame.render_widget(table, area);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(code: KeyCode, modifiers: KeyModifiers) -> Key {
+        Key { code, modifiers }
+    }
+
+    #[test]
+    fn default_keymap_has_no_conflicts() {
+        assert!(Keymap::new(&BTreeMap::new()).is_ok());
+    }
+
+    #[test]
+    fn parse_keys() {
+        assert_eq!(
+            Key::parse("G").unwrap(),
+            key(KeyCode::Char('G'), KeyModifiers::NONE)
+        );
+        assert_eq!(
+            Key::parse("ctrl-r").unwrap(),
+            key(KeyCode::Char('r'), KeyModifiers::CONTROL)
+        );
+        assert_eq!(
+            Key::parse("f12").unwrap(),
+            key(KeyCode::F(12), KeyModifiers::NONE)
+        );
+        assert!(Key::parse("ctrl-").is_err());
+    }
+
+    fn back_keys(keymap: &Keymap, screen: Screen) -> Vec<Key> {
+        keymap
+            .get_screen_bindings(screen)
+            .find(|(command, _)| *command == Command::Back)
+            .map(|(_, keys)| keys.clone())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn back_is_only_q_on_the_instances_screen() {
+        let keymap = Keymap::new(&BTreeMap::new()).unwrap();
+        assert_eq!(
+            back_keys(&keymap, Screen::Instances),
+            [Key::parse("q").unwrap()]
+        );
+        assert_eq!(back_keys(&keymap, Screen::Console).len(), 3);
+
+        let overrides = BTreeMap::from([("back".to_string(), "x".to_string())]);
+        let keymap = Keymap::new(&overrides).unwrap();
+        assert_eq!(
+            back_keys(&keymap, Screen::Instances),
+            [Key::parse("x").unwrap()]
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use crossterm::event::Event;
+use ratatui::{
+    layout::{Constraint, Rect},
+    style::{Modifier, Style, Stylize},
+    widgets::{Block, Borders, Cell, Row, Table, TableState},
+    Frame,
+};
+
+use super::keymap::{get_keymap, render_help, Command, Screen};
+use super::theme::get_theme;
+use super::{Action, HandleAction, Render, RenderHelp, View};
+use crate::cache::now;
+use crate::history::HistoryEntry;
+use crate::output::format_elapsed;
+
+/// Connections made before, most recent first
+#[derive(Default, Debug, Clone)]
+pub struct RecentList {
+    state: TableState,
+    items: Vec<HistoryEntry>,
+    visible_items: Vec<HistoryEntry>,
+    filter: String,
+    status: String,
+}
+
+impl RecentList {
+    pub fn with_items(items: Vec<HistoryEntry>) -> RecentList {
+        let mut list = RecentList::default();
+        list.update_items(items);
+        list
+    }
+
+    pub fn update_items(&mut self, items: Vec<HistoryEntry>) {
+        self.items = items;
+        self.apply_filter(self.filter.clone());
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    pub fn apply_filter(&mut self, filter: String) {
+        self.visible_items = self
+            .items
+            .iter()
+            .filter(|entry| entry.matches(&filter))
+            .cloned()
+            .collect();
+        self.filter = filter;
+        self.state.select(if self.visible_items.is_empty() {
+            None
+        } else {
+            Some(0)
+        });
+    }
+
+    pub fn set_status(&mut self, status: String) {
+        self.status = status;
+    }
+
+    pub fn next(&mut self) {
+        if self.visible_items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) if i < self.visible_items.len() - 1 => i + 1,
+            _ => 0,
+        };
+        self.state.select(Some(i));
+    }
+
+    pub fn previous(&mut self) {
+        if self.visible_items.is_empty() {
+            return;
+        }
+        let i = match self.state.selected() {
+            Some(i) if i > 0 => i - 1,
+            _ => self.visible_items.len() - 1,
+        };
+        self.state.select(Some(i));
+    }
+
+    fn current(&self) -> Option<HistoryEntry> {
+        self.state
+            .selected()
+            .and_then(|i| self.visible_items.get(i))
+            .cloned()
+    }
+}
+
+impl HandleAction for RecentList {
+    fn handle_action(&mut self, action: Event) -> Action {
+        match get_keymap().get_command(Screen::Recent, &action) {
+            Some(Command::Quit) => Action::Exit,
+            Some(Command::Regions) => Action::ShowRegions,
+            Some(Command::Search) => Action::Search,
+            Some(Command::Down) => {
+                self.next();
+                Action::Noop
+            }
+            Some(Command::Up) => {
+                self.previous();
+                Action::Noop
+            }
+            Some(Command::Open) => match self.current() {
+                Some(entry) => Action::Reconnect(entry),
+                None => Action::Noop,
+            },
+            _ => Action::Noop,
+        }
+    }
+}
+
+#[allow(refining_impl_trait)]
+impl View for RecentList {
+    fn get_widget(&self) -> Table<'_> {
+        let now = now();
+        let items: Vec<Row> = self
+            .visible_items
+            .iter()
+            .map(|entry| {
+                Row::new(vec![
+                    Cell::from(entry.name.clone()),
+                    Cell::from(entry.instance_id.clone()),
+                    Cell::from(entry.region.clone()),
+                    Cell::from(entry.profile.clone()),
+                    Cell::from(entry.mode.clone()),
+                    Cell::from(format!(
+                        "{} ago",
+                        format_elapsed(now.saturating_sub(entry.started_at))
+                    )),
+                    Cell::from(entry.duration.map(format_elapsed).unwrap_or_default()),
+                ])
+            })
+            .collect();
+        let widths = [
+            Constraint::Percentage(25),
+            Constraint::Percentage(20),
+            Constraint::Percentage(15),
+            Constraint::Percentage(15),
+            Constraint::Percentage(8),
+            Constraint::Percentage(9),
+            Constraint::Percentage(8),
+        ];
+        let title = match (&self.status, &self.filter) {
+            (status, _) if !status.is_empty() => status.clone(),
+            (_, filter) if !filter.is_empty() => {
+                format!("Recent connections matching '{}'", filter)
+            }
+            _ => "Recent connections".to_string(),
+        };
+
+        Table::new(items, widths)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .highlight_style(get_theme().highlight())
+            .highlight_symbol(">> ")
+            .header(
+                Row::new(vec![
+                    "Name",
+                    "InstanceId",
+                    "Region",
+                    "Profile",
+                    "Mode",
+                    "When",
+                    "Duration",
+                ])
+                .style(Style::default().add_modifier(Modifier::BOLD).underlined()),
+            )
+    }
+}
+
+impl Render for RecentList {
+    fn render(&mut self, frame: &mut Frame, area: Rect) {
+        let widget = self.get_widget();
+        frame.render_stateful_widget(widget, area, &mut self.state.clone());
+    }
+}
+
+impl RenderHelp for RecentList {
+    fn render_help(&mut self, frame: &mut Frame, area: Rect) {
+        render_help(
+            frame,
+            area,
+            &[
+                (Command::Quit, "Exit"),
+                (Command::Open, "Reconnect"),
+                (Command::Search, "Filter"),
+                (Command::Regions, "Regions"),
+            ],
+        );
+    }
+}
@@ -1,8 +1,9 @@
+use super::keymap::{get_keymap, render_help, Command, Screen};
+use super::theme::get_theme;
 use super::{Action, HandleAction, Render, RenderHelp, View};
-use aws_config::Region;
-use crossterm::event::{Event, KeyCode};
+use crossterm::event::Event;
 use ratatui::{
-    layout::{Constraint, Rect}, style::{Color, Modifier, Style}, text::Span, widgets::{Block, Borders, Cell, List, ListItem, ListState, Row, Table}, Frame
+    layout::Rect, widgets::{Block, Borders, List, ListItem, ListState}, Frame
 };
 
 #[derive(Default, Debug, Clone)]
@@ -10,6 +11,7 @@ pub struct RegionList {
     state: ListState,
     items: Vec<String>,
     favorites: Vec<String>,
+    title: String,
 }
 
 impl RegionList {
@@ -20,22 +22,24 @@ impl RegionList {
             state,
             items,
             favorites: Vec::new(),
+            title: String::default(),
         }
     }
 
     pub fn update_items(&mut self, items: Vec<String>) {
         self.items = items;
-        match self.state.selected_mut() {
-            Some(i) => {
-                if *i >= self.items.len() {
-                    *i = self.items.len() - 1;
-                }
+        if let Some(i) = self.state.selected_mut() {
+            if *i >= self.items.len() {
+                *i = self.items.len() - 1;
             }
-            None => {}
         }
         self.sort_list();
     }
 
+    pub fn set_title(&mut self, title: String) {
+        self.title = title;
+    }
+
     pub fn set_favorites(&mut self, favorites: Vec<String>) {
         self.favorites = favorites;
         self.sort_list();
@@ -88,25 +92,25 @@ impl RegionList {
 
 impl HandleAction for RegionList {
     fn handle_action(&mut self, action: Event) -> Action {
-        match action {
-            Event::Key(key) => match key.code {
-                KeyCode::Char('q') => Action::Exit,
-                KeyCode::Char('h') => Action::Hide(self.current().unwrap()),
-                KeyCode::Char('r') => Action::Reset,
-                KeyCode::Char('*') => Action::ToggleFavorite(self.current().unwrap()),
-                KeyCode::Down => {
-                    self.next();
-                    Action::Noop
-                }
-                KeyCode::Up => {
-                    self.previous();
-                    Action::Noop
-                }
-                KeyCode::Right | KeyCode::Enter => match self.current() {
-                    Some(str) => Action::Return(str.to_owned()),
-                    None => Action::Noop,
-                },
-                _ => Action::Noop,
+        match get_keymap().get_command(Screen::Regions, &action) {
+            Some(Command::Quit) => Action::Exit,
+            Some(Command::Hide) => Action::Hide(self.current().unwrap()),
+            Some(Command::ResetRegions) => Action::Reset,
+            Some(Command::Favorite) => Action::ToggleFavorite(self.current().unwrap()),
+            Some(Command::Filters) => Action::EditFilters,
+            Some(Command::Bookmarks) => Action::ShowBookmarks,
+            Some(Command::Recent) => Action::ShowRecent,
+            Some(Command::Down) => {
+                self.next();
+                Action::Noop
+            }
+            Some(Command::Up) => {
+                self.previous();
+                Action::Noop
+            }
+            Some(Command::Open) => match self.current() {
+                Some(str) => Action::Return(str.to_owned()),
+                None => Action::Noop,
             },
             _ => Action::Noop,
         }
@@ -115,7 +119,7 @@ impl HandleAction for RegionList {
 
 #[allow(refining_impl_trait)]
 impl View for RegionList {
-    fn get_widget(&self) -> List {
+    fn get_widget(&self) -> List<'_> {
         let items: Vec<ListItem> = self
             .items
             .iter()
@@ -131,12 +135,8 @@ impl View for RegionList {
 
         // Create a List from all list items and highlight the currently selected one
         List::new(items)
-            .block(Block::default().borders(Borders::ALL))
-            .highlight_style(
-                Style::default()
-                    .bg(Color::LightGreen)
-                    .add_modifier(Modifier::BOLD),
-            )
+            .block(Block::default().borders(Borders::ALL).title(self.title.as_str()))
+            .highlight_style(get_theme().highlight())
             .highlight_symbol(">> ")
     }
 }
@@ -150,33 +150,18 @@ impl Render for RegionList {
 
 impl RenderHelp for RegionList {
     fn render_help(&mut self, frame: &mut Frame, area: Rect) {
-        let rows = vec![Row::new(vec![
-            Cell::from(Span::styled(
-                "'q' Exit",
-                Style::default().fg(Color::White),
-            )),
-            Cell::from(Span::styled(
-                "'h' Hide",
-                Style::default().fg(Color::White),
-            )),
-            Cell::from(Span::styled(
-                "'r' Reset regions",
-                Style::default().fg(Color::White),
-            )),
-            Cell::from(Span::styled(
-                "'*' Toggle Favorite",
-                Style::default().fg(Color::White),
-            )),
-        ])];
-        let table = Table::new(
-            rows,
-            vec![
-                Constraint::Min(10),
-                Constraint::Min(10),
-                Constraint::Min(10),
-                Constraint::Min(10),
+        render_help(
+            frame,
+            area,
+            &[
+                (Command::Quit, "Exit"),
+                (Command::Hide, "Hide"),
+                (Command::ResetRegions, "Reset regions"),
+                (Command::Favorite, "Toggle Favorite"),
+                (Command::Filters, "Server filters"),
+                (Command::Bookmarks, "Bookmarks"),
+                (Command::Recent, "Recent"),
             ],
         );
-        frame.render_widget(table, area);
     }
-}
\ No newline at end of file
+}
@@ -25,6 +25,18 @@ impl Default for TextInput {
 }
 
 impl TextInput {
+    pub fn with_prompt(prompt: &str) -> TextInput {
+        TextInput {
+            prompt: prompt.into(),
+            ..TextInput::default()
+        }
+    }
+
+    pub fn set_value(&mut self, value: String) {
+        self.search_cursor_position = value.len();
+        self.search_input = value;
+    }
+
     pub fn get_cursor_position(&self) -> usize {
         self.search_cursor_position + self.prompt.len()
     }
@@ -131,8 +143,8 @@ impl HandleAction for TextInput {
 
 #[allow(refining_impl_trait)]
 impl View for TextInput {
-    fn get_widget(&self) -> Paragraph {
-        let text: String = format!("Searching: {}", self.search_input);
+    fn get_widget(&self) -> Paragraph<'_> {
+        let text: String = format!("{}{}", self.prompt, self.search_input);
         Paragraph::new(Text::from(text))
     }
 }
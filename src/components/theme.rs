@@ -0,0 +1,225 @@
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use anyhow::{anyhow, bail, Context, Result};
+use ratatui::style::{Color, Modifier, Style};
+
+use crate::app::config::{Palette, ThemeSection};
+
+/// Built-in themes, by name
+const BUILT_IN: &[(&str, Theme)] = &[
+    ("dark", Theme::DARK),
+    ("light", Theme::LIGHT),
+    ("high-contrast", Theme::HIGH_CONTRAST),
+];
+
+/// Colors of the UI. Components get their styles from the active theme rather than naming
+/// colors, so a theme or `NO_COLOR` applies to every screen.
+#[derive(Debug, Clone, Copy)]
+pub struct Theme {
+    text: Color,
+    active_tab: Color,
+    highlight_fg: Color,
+    highlight_bg: Color,
+    error: Color,
+    search_match: Color,
+    cpu: Color,
+    network_in: Color,
+    network_out: Color,
+    status_check: Color,
+    /// Without colors, highlights are shown reversed instead
+    colored: bool,
+}
+
+impl Theme {
+    /// For dark terminal backgrounds, the default
+    const DARK: Theme = Theme {
+        text: Color::White,
+        active_tab: Color::Yellow,
+        highlight_fg: Color::Reset,
+        highlight_bg: Color::LightGreen,
+        error: Color::LightRed,
+        search_match: Color::Yellow,
+        cpu: Color::LightBlue,
+        network_in: Color::LightGreen,
+        network_out: Color::LightYellow,
+        status_check: Color::LightRed,
+        colored: true,
+    };
+
+    /// For light terminal backgrounds, text keeps the terminal's own color
+    const LIGHT: Theme = Theme {
+        text: Color::Reset,
+        active_tab: Color::Blue,
+        highlight_fg: Color::White,
+        highlight_bg: Color::Blue,
+        error: Color::Red,
+        search_match: Color::Magenta,
+        cpu: Color::Blue,
+        network_in: Color::Green,
+        network_out: Color::Magenta,
+        status_check: Color::Red,
+        colored: true,
+    };
+
+    const HIGH_CONTRAST: Theme = Theme {
+        text: Color::White,
+        active_tab: Color::LightYellow,
+        highlight_fg: Color::Black,
+        highlight_bg: Color::LightYellow,
+        error: Color::LightRed,
+        search_match: Color::LightCyan,
+        cpu: Color::LightCyan,
+        network_in: Color::LightGreen,
+        network_out: Color::LightYellow,
+        status_check: Color::LightRed,
+        colored: true,
+    };
+
+    /// The terminal's own colors, for `NO_COLOR` and `--no-color`
+    const PLAIN: Theme = Theme {
+        text: Color::Reset,
+        active_tab: Color::Reset,
+        highlight_fg: Color::Reset,
+        highlight_bg: Color::Reset,
+        error: Color::Reset,
+        search_match: Color::Reset,
+        cpu: Color::Reset,
+        network_in: Color::Reset,
+        network_out: Color::Reset,
+        status_check: Color::Reset,
+        colored: false,
+    };
+
+    /// The theme named in the `[theme]` section, a built-in theme or a palette.
+    /// The section is validated even without colors, so a typo doesn't go unnoticed.
+    pub fn new(section: &ThemeSection, no_color: bool) -> Result<Theme> {
+        let name = section.name.as_deref().unwrap_or("dark");
+        let theme = match section.palettes.get(name) {
+            Some(palette) => Theme::with_palette(palette)
+                .with_context(|| format!("invalid palette '{}'", name))?,
+            None => Theme::built_in(name)?,
+        };
+        Ok(if no_color { Theme::PLAIN } else { theme })
+    }
+
+    /// A built-in theme with the colors set in the palette
+    fn with_palette(palette: &Palette) -> Result<Theme> {
+        let mut theme = Theme::built_in(palette.base.as_deref().unwrap_or("dark"))?;
+        set_color(&mut theme.text, &palette.text, "text")?;
+        set_color(&mut theme.active_tab, &palette.active_tab, "active_tab")?;
+        set_color(
+            &mut theme.highlight_fg,
+            &palette.highlight_fg,
+            "highlight_fg",
+        )?;
+        set_color(
+            &mut theme.highlight_bg,
+            &palette.highlight_bg,
+            "highlight_bg",
+        )?;
+        set_color(&mut theme.error, &palette.error, "error")?;
+        set_color(
+            &mut theme.search_match,
+            &palette.search_match,
+            "search_match",
+        )?;
+        set_color(&mut theme.cpu, &palette.cpu, "cpu")?;
+        set_color(&mut theme.network_in, &palette.network_in, "network_in")?;
+        set_color(&mut theme.network_out, &palette.network_out, "network_out")?;
+        set_color(
+            &mut theme.status_check,
+            &palette.status_check,
+            "status_check",
+        )?;
+        Ok(theme)
+    }
+
+    fn built_in(name: &str) -> Result<Theme> {
+        match BUILT_IN.iter().find(|(built_in, _)| *built_in == name) {
+            Some((_, theme)) => Ok(*theme),
+            None => bail!(
+                "unknown theme '{}', expected dark, light, high-contrast or a palette",
+                name
+            ),
+        }
+    }
+
+    /// Help bar and tabs
+    pub fn text(&self) -> Style {
+        Style::default().fg(self.text)
+    }
+
+    pub fn active_tab(&self) -> Style {
+        if self.colored {
+            Style::default().fg(self.active_tab)
+        } else {
+            Style::default().add_modifier(Modifier::REVERSED)
+        }
+    }
+
+    /// Selected row of lists and tables
+    pub fn highlight(&self) -> Style {
+        let style = Style::default().add_modifier(Modifier::BOLD);
+        if self.colored {
+            style.fg(self.highlight_fg).bg(self.highlight_bg)
+        } else {
+            style.add_modifier(Modifier::REVERSED)
+        }
+    }
+
+    pub fn error(&self) -> Style {
+        Style::default().fg(self.error).add_modifier(Modifier::BOLD)
+    }
+
+    /// Console output lines matching the search
+    pub fn search_match(&self) -> Style {
+        if self.colored {
+            Style::default().fg(self.search_match)
+        } else {
+            Style::default().add_modifier(Modifier::BOLD)
+        }
+    }
+
+    /// Metrics charts: CPU, network in, network out and status checks
+    pub fn charts(&self) -> [Color; 4] {
+        [
+            self.cpu,
+            self.network_in,
+            self.network_out,
+            self.status_check,
+        ]
+    }
+}
+
+/// Override a color of a theme with one set in a palette
+fn set_color(color: &mut Color, value: &Option<String>, key: &str) -> Result<()> {
+    if let Some(value) = value {
+        *color = Color::from_str(value).map_err(|_| {
+            anyhow!(
+                "invalid color '{}' for {}, expected a name such as lightgreen, an index from 0 \
+                 to 255 or #rrggbb",
+                value,
+                key
+            )
+        })?;
+    }
+    Ok(())
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::DARK
+    }
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Set the theme, must be called before the UI starts
+pub fn set_theme(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+pub fn get_theme() -> &'static Theme {
+    THEME.get_or_init(Theme::default)
+}
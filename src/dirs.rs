@@ -0,0 +1,57 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use home::home_dir;
+
+/// A base directory from the XDG base directory spec, or its default under the home directory.
+/// Relative paths are invalid per the spec and ignored.
+fn get_base_dir(variable: &str, default: &str) -> Result<PathBuf> {
+    match std::env::var_os(variable).map(PathBuf::from) {
+        Some(path) if path.is_absolute() => Ok(path),
+        _ => {
+            let Some(home_dir) = home_dir() else {
+                return Result::Err(anyhow::anyhow!("Could not find home directory"));
+            };
+            Ok(home_dir.join(default))
+        }
+    }
+}
+
+/// `$XDG_CONFIG_HOME/sm_connect`, for files the user edits
+pub fn get_config_dir() -> Result<PathBuf> {
+    Ok(get_base_dir("XDG_CONFIG_HOME", ".config")?.join("sm_connect"))
+}
+
+/// `$XDG_CACHE_HOME/sm_connect`, for data that can be fetched again
+pub fn get_cache_dir() -> Result<PathBuf> {
+    Ok(get_base_dir("XDG_CACHE_HOME", ".cache")?.join("sm_connect"))
+}
+
+/// `$XDG_STATE_HOME/sm_connect`, for history that should survive but isn't worth backing up
+pub fn get_state_dir() -> Result<PathBuf> {
+    Ok(get_base_dir("XDG_STATE_HOME", ".local/state")?.join("sm_connect"))
+}
+
+/// Write to a temporary file next to `path` and rename it over `path`, so readers see either the
+/// old or the new content. The permissions of the previous file are kept.
+pub fn write_atomically(path: &Path, content: &str) -> Result<()> {
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(format!(".{}.tmp", std::process::id()));
+    let temp_path = PathBuf::from(temp_path);
+    let result = (|| -> Result<()> {
+        let mut file = File::create(&temp_path)?;
+        file.write_all(content.as_bytes())?;
+        file.sync_all()?;
+        if let Ok(metadata) = std::fs::metadata(path) {
+            std::fs::set_permissions(&temp_path, metadata.permissions())?;
+        }
+        std::fs::rename(&temp_path, path)?;
+        Ok(())
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp_path);
+    }
+    result
+}
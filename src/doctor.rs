@@ -0,0 +1,321 @@
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal};
+use std::process::{Command, ExitCode};
+
+use anyhow::Result;
+use aws_config::Region;
+use crossterm::terminal::{disable_raw_mode, enable_raw_mode};
+
+use crate::app::config::Config;
+use crate::aws::{current_profile, fetch_caller_identity, set_aws_settings, AwsError, AwsSettings};
+use crate::cli::Cli;
+use crate::components::keymap::Keymap;
+use crate::components::theme::Theme;
+use crate::exit::EXIT_ERROR;
+
+const INSTALL_CLI_URL: &str =
+    "https://docs.aws.amazon.com/cli/latest/userguide/getting-started-install.html";
+const INSTALL_PLUGIN_URL: &str = "https://docs.aws.amazon.com/systems-manager/latest/userguide/session-manager-working-with-install-plugin.html";
+
+/// STS answers in every region, this one is used when no region is configured
+const FALLBACK_REGION: &str = "us-east-1";
+
+/// Smallest window the TUI is laid out for
+const MIN_COLUMNS: u16 = 80;
+const MIN_ROWS: u16 = 24;
+
+enum Status {
+    Ok,
+    /// Works, but some of the UI may be degraded
+    Warning,
+    Failed,
+}
+
+struct Check {
+    name: &'static str,
+    status: Status,
+    detail: String,
+    /// What the user can do about a warning or a failure
+    fix: Option<String>,
+}
+
+impl Check {
+    fn ok(name: &'static str, detail: String) -> Check {
+        Check {
+            name,
+            status: Status::Ok,
+            detail,
+            fix: None,
+        }
+    }
+
+    fn warning(name: &'static str, detail: String, fix: String) -> Check {
+        Check {
+            name,
+            status: Status::Warning,
+            detail,
+            fix: Some(fix),
+        }
+    }
+
+    fn failed(name: &'static str, detail: String, fix: String) -> Check {
+        Check {
+            name,
+            status: Status::Failed,
+            detail,
+            fix: Some(fix),
+        }
+    }
+
+    fn print(&self) {
+        let label = match self.status {
+            Status::Ok => "[ok]  ",
+            Status::Warning => "[warn]",
+            Status::Failed => "[fail]",
+        };
+        // Errors such as TOML parse errors span several lines
+        let detail: Vec<&str> = self
+            .detail
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect();
+        println!("{} {}: {}", label, self.name, detail.join(" "));
+        if let Some(ref fix) = self.fix {
+            println!("       fix: {}", fix);
+        }
+    }
+}
+
+/// Run a program with `--version`, returning the first line it printed.
+/// AWS CLI v1 prints its version to stderr, v2 and the plugin to stdout.
+fn get_version(program: &str) -> io::Result<String> {
+    let output = Command::new(program).arg("--version").output()?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "`{} --version` failed: {}",
+            program,
+            stderr.trim()
+        )));
+    }
+    Ok(stdout
+        .lines()
+        .chain(stderr.lines())
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or("unknown version")
+        .to_string())
+}
+
+fn check_program(name: &'static str, program: &str, install_url: &str) -> Check {
+    match get_version(program) {
+        Ok(version) => Check::ok(name, version),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Check::failed(
+            name,
+            format!("`{}` is not on the PATH", program),
+            format!(
+                "Install it from {}, then open a new shell so the PATH is reloaded",
+                install_url
+            ),
+        ),
+        Err(e) => Check::failed(
+            name,
+            e.to_string(),
+            format!("Reinstall it from {}", install_url),
+        ),
+    }
+}
+
+fn check_config(config: &Result<Config>) -> Check {
+    match config {
+        Ok(config) => {
+            if let Err(e) = Keymap::new(&config.get_keybindings()) {
+                return Check::failed(
+                    "Config",
+                    format!("invalid [keybindings]: {:#}", e),
+                    "Bind the command to another key, or unbind the other one with an empty string"
+                        .to_string(),
+                );
+            }
+            if let Err(e) = Theme::new(&config.get_theme(), false) {
+                return Check::failed(
+                    "Config",
+                    format!("invalid [theme]: {:#}", e),
+                    "Name a built-in theme or a palette from [theme.palettes]".to_string(),
+                );
+            }
+            let files: Vec<String> = config
+                .get_files()
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect();
+            let detail = if files.is_empty() {
+                "no config file, using the defaults".to_string()
+            } else {
+                files.join(", ")
+            };
+            Check::ok("Config", detail)
+        }
+        Err(e) => Check::failed(
+            "Config",
+            format!("{:#}", e),
+            "Fix the value named in the error, or move the file away to start from the defaults"
+                .to_string(),
+        ),
+    }
+}
+
+async fn check_credentials(region: Option<String>) -> Check {
+    let region = region.unwrap_or_else(|| FALLBACK_REGION.to_string());
+    let profile = current_profile();
+    match fetch_caller_identity(Region::new(region)).await {
+        Ok(identity) => Check::ok(
+            "Credentials",
+            format!("{} via profile {}", identity, profile),
+        ),
+        Err(e) => {
+            let hint = e
+                .chain()
+                .find_map(|cause| cause.downcast_ref::<AwsError>())
+                .map(|error| error.kind.get_hint())
+                .unwrap_or("Check the AWS configuration of the profile.");
+            Check::failed(
+                "Credentials",
+                format!("profile {}: {:#}", profile, e),
+                format!(
+                    "{} Pick another profile with --profile, or `aws configure --profile {}`",
+                    hint, profile
+                ),
+            )
+        }
+    }
+}
+
+/// The TUI needs a terminal it can switch to raw mode, and is drawn with colors and symbols
+/// that need a reasonably sized window, a color terminal and a UTF-8 locale
+fn check_terminal(cli: &Cli) -> Vec<Check> {
+    let mut checks = Vec::new();
+    let has_terminal =
+        io::stdout().is_terminal() || OpenOptions::new().write(true).open("/dev/tty").is_ok();
+    if !has_terminal {
+        checks.push(Check::failed(
+            "Terminal",
+            "stdout is not a terminal and /dev/tty can't be opened".to_string(),
+            "Run sm_connect from an interactive terminal. Scripts can use `list`, or `connect` \
+             with --region and a query matching a single instance"
+                .to_string(),
+        ));
+        return checks;
+    }
+
+    let term = std::env::var("TERM").unwrap_or_default();
+    checks.push(if term.is_empty() || term == "dumb" {
+        Check::failed(
+            "Terminal",
+            format!("TERM is '{}'", term),
+            "Set TERM to your terminal type, e.g. `export TERM=xterm-256color`".to_string(),
+        )
+    } else {
+        match enable_raw_mode().and_then(|_| disable_raw_mode()) {
+            Ok(_) => Check::ok("Terminal", format!("{}, raw mode supported", term)),
+            Err(e) => Check::failed(
+                "Terminal",
+                format!("raw mode can't be enabled: {}", e),
+                "Use a full terminal emulator rather than an IDE output pane or a pipe".to_string(),
+            ),
+        }
+    });
+
+    let colorterm = std::env::var("COLORTERM").unwrap_or_default();
+    checks.push(if cli.colors_disabled() {
+        Check::ok("Colors", "disabled by NO_COLOR or --no-color".to_string())
+    } else if term.contains("256color") || colorterm == "truecolor" || colorterm == "24bit" {
+        Check::ok("Colors", "256 colors or more".to_string())
+    } else {
+        Check::warning(
+            "Colors",
+            "only the basic colors may be available".to_string(),
+            "Set TERM to a 256 color variant, e.g. `export TERM=xterm-256color`".to_string(),
+        )
+    });
+
+    if let Ok((columns, rows)) = crossterm::terminal::size() {
+        checks.push(if columns >= MIN_COLUMNS && rows >= MIN_ROWS {
+            Check::ok("Window size", format!("{}x{}", columns, rows))
+        } else {
+            Check::warning(
+                "Window size",
+                format!("{}x{}, columns and help are cut off", columns, rows),
+                format!(
+                    "Enlarge the window to at least {}x{}",
+                    MIN_COLUMNS, MIN_ROWS
+                ),
+            )
+        });
+    }
+
+    // The first of these that is set decides the character set, as in the C library
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty())
+        .unwrap_or_default();
+    let lowercase = locale.to_lowercase();
+    checks.push(
+        if lowercase.contains("utf-8") || lowercase.contains("utf8") {
+            Check::ok("Locale", locale)
+        } else {
+            Check::warning(
+                "Locale",
+                format!("'{}' is not UTF-8, symbols such as ★ may not show", locale),
+                "Set a UTF-8 locale, e.g. `export LANG=en_US.UTF-8`".to_string(),
+            )
+        },
+    );
+    checks
+}
+
+/// Check everything sessions depend on, printing a line per check and how to fix failures.
+/// Fails when any check failed, warnings only point at a degraded UI.
+pub async fn run_doctor(cli: &Cli, config: Result<Config>) -> ExitCode {
+    let mut checks = vec![
+        check_program("AWS CLI", "aws", INSTALL_CLI_URL),
+        check_program(
+            "Session Manager plugin",
+            "session-manager-plugin",
+            INSTALL_PLUGIN_URL,
+        ),
+        check_config(&config),
+    ];
+
+    // Credentials are still checked with a broken config, with the profile from the command line
+    let region = match config {
+        Ok(ref config) => {
+            set_aws_settings(config.get_aws_settings());
+            config.get_region()
+        }
+        Err(_) => {
+            set_aws_settings(AwsSettings {
+                profile: cli.profile.clone(),
+                ..AwsSettings::default()
+            });
+            cli.region.clone()
+        }
+    };
+    checks.push(check_credentials(region).await);
+    checks.extend(check_terminal(cli));
+
+    for check in &checks {
+        check.print();
+    }
+    if checks
+        .iter()
+        .any(|check| matches!(check.status, Status::Failed))
+    {
+        ExitCode::from(EXIT_ERROR)
+    } else {
+        ExitCode::SUCCESS
+    }
+}
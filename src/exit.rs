@@ -0,0 +1,62 @@
+use std::process::{ExitCode, ExitStatus};
+
+use thiserror::Error;
+
+use crate::app::RuntimeError;
+use crate::aws::{AwsError, AwsErrorKind};
+
+// Exit codes, documented in the README. Wrapper scripts rely on them to tell apart a user quitting
+// from expired credentials, so they must not change. A session that ran exits with its own code.
+pub const EXIT_ERROR: u8 = 1;
+pub const EXIT_NO_MATCH: u8 = 3;
+pub const EXIT_AUTH: u8 = 4;
+pub const EXIT_FETCH: u8 = 5;
+pub const EXIT_SESSION_START: u8 = 6;
+pub const EXIT_CANCELLED: u8 = 130;
+
+#[derive(Error, Debug)]
+pub enum Failure {
+    #[error("No instance matches '{0}'")]
+    NoMatch(String),
+    #[error("No bookmark named '@{0}'")]
+    NoBookmark(String),
+    #[error("Failed to start the session: {0}. `sm_connect doctor` checks the setup")]
+    SessionStart(String),
+}
+
+/// Map an error to its documented exit code
+pub fn get_exit_code(error: &anyhow::Error) -> u8 {
+    if let Some(RuntimeError::UserExit) = error.downcast_ref() {
+        return EXIT_CANCELLED;
+    }
+    if let Some(failure) = error.downcast_ref() {
+        return match failure {
+            Failure::NoMatch(_) | Failure::NoBookmark(_) => EXIT_NO_MATCH,
+            Failure::SessionStart(_) => EXIT_SESSION_START,
+        };
+    }
+    match error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<AwsError>())
+    {
+        Some(AwsError {
+            kind: AwsErrorKind::Auth | AwsErrorKind::AccessDenied,
+            ..
+        }) => EXIT_AUTH,
+        Some(_) => EXIT_FETCH,
+        None => EXIT_ERROR,
+    }
+}
+
+/// Pass the exit status of a session through, using the shell convention of 128 + signal number
+/// when it was killed
+pub fn from_exit_status(status: ExitStatus) -> ExitCode {
+    #[cfg(unix)]
+    if let Some(signal) = std::os::unix::process::ExitStatusExt::signal(&status) {
+        return ExitCode::from(128 + signal as u8);
+    }
+    match status.code() {
+        Some(code) => ExitCode::from(code as u8),
+        None => ExitCode::from(EXIT_ERROR),
+    }
+}
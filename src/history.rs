@@ -0,0 +1,190 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::aws::InstanceInfo;
+use crate::cache::now;
+use crate::dirs::{get_state_dir, write_atomically};
+
+/// Entries kept when the history file is compacted
+const MAX_ENTRIES: usize = 500;
+
+/// Size in bytes the history file is compacted at, about twice `MAX_ENTRIES` entries, so it's
+/// only read back once in a while rather than after every connection
+const COMPACT_SIZE: u64 = 200 * 1024;
+
+/// A connection that was made, appended to the history file when it ends
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub instance_id: String,
+    pub name: String,
+    pub region: String,
+    pub profile: String,
+    /// `shell`, `forward`, `exec` or `tmux`
+    pub mode: String,
+    /// Unix time the connection started at
+    pub started_at: u64,
+    /// In seconds, unknown for sessions handed over to tmux
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+}
+
+impl HistoryEntry {
+    pub fn new(instance: &InstanceInfo, profile: String, mode: &str) -> HistoryEntry {
+        HistoryEntry {
+            instance_id: instance.get_instance_id(),
+            name: instance.get_name(),
+            region: instance.get_region().to_string(),
+            profile,
+            mode: mode.to_string(),
+            started_at: now(),
+            duration: None,
+        }
+    }
+
+    /// Whether the entry matches a case insensitive substring of its name, id, region, profile or mode
+    pub fn matches(&self, filter: &str) -> bool {
+        let filter = filter.to_lowercase();
+        [
+            &self.name,
+            &self.instance_id,
+            &self.region,
+            &self.profile,
+            &self.mode,
+        ]
+        .iter()
+        .any(|field| field.to_lowercase().contains(&filter))
+    }
+}
+
+fn get_history_path() -> Result<PathBuf> {
+    Ok(get_state_dir()?.join("history.jsonl"))
+}
+
+fn read_entries(path: &Path) -> Result<Vec<HistoryEntry>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    // A line cut short by a crash is skipped rather than losing the whole history
+    Ok(std::fs::read_to_string(path)?
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Append a connection to the history, compacting the file once it grows past `COMPACT_SIZE`
+pub fn record(entry: &HistoryEntry) -> Result<()> {
+    append(&get_history_path()?, entry)
+}
+
+fn append(path: &Path, entry: &HistoryEntry) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    writeln!(file, "{}", serde_json::to_string(entry)?)?;
+
+    if file.metadata()?.len() < COMPACT_SIZE {
+        return Ok(());
+    }
+    let entries = read_entries(path)?;
+    if entries.len() > MAX_ENTRIES {
+        let kept: String = entries[entries.len() - MAX_ENTRIES..]
+            .iter()
+            .map(|entry| Ok(serde_json::to_string(entry)? + "\n"))
+            .collect::<Result<String>>()?;
+        write_atomically(path, &kept)?;
+    }
+    Ok(())
+}
+
+/// The last connection to every instance, most recent first.
+/// History is a convenience, so an unreadable file shows as empty.
+pub fn load_recent() -> Vec<HistoryEntry> {
+    let entries = get_history_path()
+        .and_then(|path| read_entries(&path))
+        .unwrap_or_default();
+    most_recent(entries)
+}
+
+fn most_recent(entries: Vec<HistoryEntry>) -> Vec<HistoryEntry> {
+    let mut recent: Vec<HistoryEntry> = Vec::new();
+    for entry in entries.into_iter().rev() {
+        let seen = recent.iter().any(|existing| {
+            existing.instance_id == entry.instance_id
+                && existing.region == entry.region
+                && existing.profile == entry.profile
+        });
+        if !seen {
+            recent.push(entry);
+        }
+    }
+    recent
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(instance_id: &str, started_at: u64) -> HistoryEntry {
+        HistoryEntry {
+            instance_id: instance_id.to_string(),
+            name: "web".to_string(),
+            region: "eu-west-1".to_string(),
+            profile: "prod".to_string(),
+            mode: "shell".to_string(),
+            started_at,
+            duration: Some(60),
+        }
+    }
+
+    #[test]
+    fn history_is_compacted_to_the_last_entries() {
+        let path =
+            std::env::temp_dir().join(format!("sm_connect_history_{}.jsonl", std::process::id()));
+        let line = serde_json::to_string(&entry("i-0123", 0)).unwrap() + "\n";
+        // Two lines short of the size the file is compacted at
+        let count = (COMPACT_SIZE as usize).div_ceil(line.len()) - 2;
+        std::fs::write(&path, line.repeat(count)).unwrap();
+
+        append(&path, &entry("i-0123", 1)).unwrap();
+        assert_eq!(read_entries(&path).unwrap().len(), count + 1);
+
+        append(&path, &entry("i-0456", 2)).unwrap();
+        let entries = read_entries(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries[MAX_ENTRIES - 2].started_at, 1);
+        assert_eq!(entries[MAX_ENTRIES - 1].instance_id, "i-0456");
+    }
+
+    #[test]
+    fn cut_lines_are_skipped() {
+        let path =
+            std::env::temp_dir().join(format!("sm_connect_cut_{}.jsonl", std::process::id()));
+        let line = serde_json::to_string(&entry("i-0123", 1)).unwrap();
+        std::fs::write(&path, format!("{}\n{}", line, &line[..20])).unwrap();
+        let entries = read_entries(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(entries.len(), 1);
+    }
+
+    #[test]
+    fn recent_keeps_the_last_connection_to_every_instance() {
+        let mut other_profile = entry("i-0123", 3);
+        other_profile.profile = "dev".to_string();
+        let recent = most_recent(vec![
+            entry("i-0123", 1),
+            entry("i-0456", 2),
+            other_profile,
+            entry("i-0123", 4),
+        ]);
+        let started: Vec<u64> = recent.iter().map(|entry| entry.started_at).collect();
+        assert_eq!(started, [4, 3, 2]);
+    }
+}
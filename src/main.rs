@@ -1,58 +1,451 @@
 mod aws;
-use aws::InstanceInfo;
+use aws::{
+    current_profile, fetch_instances, profile_override, set_aws_settings, set_profile, InstanceInfo,
+};
+mod cache;
+use cache::save_inventory;
+mod cli;
+use cli::{BookmarkCommand, Cli, Command, ConfigCommand};
 mod ui;
 use ui::{restore_terminal, setup_terminal};
 mod app;
+use app::config::{Bookmark, Config};
 use app::App;
-use std::process::Command;
+mod completions;
+use completions::{instance_candidates, print_completions};
 mod components;
+use components::keymap::{set_keymap, Keymap};
+use components::theme::{set_theme, Theme};
+mod dirs;
+mod doctor;
+use doctor::run_doctor;
+mod exit;
+use exit::{from_exit_status, get_exit_code, Failure, EXIT_CANCELLED};
+mod history;
+use history::{record, HistoryEntry};
+mod output;
+use output::{format_instances, print_output, render_template, validate_fields, validate_template};
+mod session;
+use session::{connect, print_command, shell_quote, SessionKind};
+mod sort;
+mod ssh_config;
+use ssh_config::{get_default_ssh_config_path, get_other_aliases, render_block, update_ssh_config};
+mod tmux;
+use tmux::{in_tmux, open_in_tmux, TmuxLayout};
 
 use anyhow::{Context, Result};
-use signal_hook::{consts::signal::*, iterator::Signals};
+use aws_config::Region;
+use clap::Parser;
+use std::collections::HashSet;
+use std::process::ExitCode;
+use std::time::Instant;
 
 #[tokio::main]
-async fn main() -> Result<()> {
+async fn main() -> ExitCode {
+    match run().await {
+        Ok(code) => code,
+        Err(e) => {
+            let code = get_exit_code(&e);
+            if code != EXIT_CANCELLED {
+                eprintln!("Error: {:?}", e);
+            }
+            ExitCode::from(code)
+        }
+    }
+}
+
+async fn run() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    let config = load_config(&cli);
+    // The doctor reports a broken config instead of failing on it
+    if let Some(Command::Doctor) = cli.command {
+        return Ok(run_doctor(&cli, config).await);
+    }
+    let mut config = config?;
+    set_aws_settings(config.get_aws_settings());
+    set_keymap(Keymap::new(&config.get_keybindings()).context("invalid [keybindings]")?);
+    let theme = Theme::new(&config.get_theme(), cli.colors_disabled());
+    set_theme(theme.context("invalid [theme]")?);
+    let region = config.get_region();
+
+    match cli
+        .command
+        .clone()
+        .unwrap_or(Command::Connect {
+            query: None,
+            tmux: TmuxLayout::Panes,
+            synchronize_panes: false,
+        }) {
+        Command::Connect {
+            query,
+            tmux,
+            synchronize_panes,
+        } => {
+            // Several instances can be marked in the TUI when they can be opened side by side
+            let mut instances = get_targets(config, region, query, in_tmux()).await?;
+            if instances.len() == 1 {
+                return start_session(instances.remove(0), SessionKind::Shell, cli.print_command);
+            }
+            if cli.print_command {
+                for instance in instances {
+                    print_command(&instance, &SessionKind::Shell);
+                }
+            } else {
+                open_in_tmux(&instances, tmux, synchronize_panes)?;
+                for instance in &instances {
+                    record_connection(HistoryEntry::new(instance, current_profile(), "tmux"));
+                }
+            }
+        }
+        Command::List { format, fields } => {
+            validate_fields(&fields)?;
+            let region = require_region(region)?;
+            let instances = fetch_region(&config, &region).await?;
+            print_output(&format_instances(&instances, &fields, format))?;
+        }
+        Command::Forward {
+            tunnel,
+            target,
+            remote_port,
+            local_port,
+            remote_host,
+        } => {
+            let tunnel = match tunnel {
+                Some(name) => Some(
+                    config
+                        .get_tunnel(&name)
+                        .with_context(|| format!("No tunnel named '{}' in the config", name))?,
+                ),
+                None => None,
+            };
+            let remote_port = remote_port
+                .or(tunnel.as_ref().map(|tunnel| tunnel.remote_port))
+                .context("--remote-port is required")?;
+            let kind = SessionKind::PortForward {
+                local_port: local_port
+                    .or(tunnel.as_ref().and_then(|tunnel| tunnel.local_port))
+                    .unwrap_or(remote_port),
+                remote_port,
+                remote_host: remote_host.or(tunnel
+                    .as_ref()
+                    .and_then(|tunnel| tunnel.remote_host.clone())),
+            };
+            // The tunnel's region only yields to --region, it is more specific than the config
+            let region = match tunnel.as_ref().and_then(|tunnel| tunnel.region.clone()) {
+                Some(tunnel_region) if cli.region.is_none() => Some(tunnel_region),
+                _ => region,
+            };
+            let target = target.or(tunnel.map(|tunnel| tunnel.target));
+            let instance = get_target(config, region, target).await?;
+            return start_session(instance, kind, cli.print_command);
+        }
+        Command::Exec { target, command } => {
+            let kind = SessionKind::Exec {
+                // Each argument stays one word on the instance, as with a local command
+                command: command
+                    .iter()
+                    .map(|arg| shell_quote(arg))
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            };
+            let instance = get_target(config, region, target).await?;
+            return start_session(instance, kind, cli.print_command);
+        }
+        Command::Pick { format } => {
+            validate_template(&format)?;
+            let mut output = String::new();
+            for instance in pick_instances(config, region, None, true).await? {
+                output.push_str(&render_template(&format, &instance)?);
+                output.push('\n');
+            }
+            print_output(&output)?;
+        }
+        Command::Regions => {
+            let favorites = config.get_favorite_regions();
+            let (mut regions, others): (Vec<String>, Vec<String>) = config
+                .get_visible_regions()
+                .into_iter()
+                .partition(|region| favorites.contains(region));
+            regions.extend(others);
+            let output: String = regions
+                .iter()
+                .map(|region| format!("{}\n", region))
+                .collect();
+            print_output(&output)?;
+        }
+        Command::SshConfig {
+            write,
+            ssh_config,
+            prefix,
+            user,
+        } => {
+            let region = require_region(region)?;
+            let instances = fetch_region(&config, &region).await?;
+            let profile = current_profile();
+            // Pin the profile in use now, ssh may run without AWS_PROFILE set
+            let pinned_profile = profile_override().or_else(|| std::env::var("AWS_PROFILE").ok());
+            let path = match (write, ssh_config) {
+                (false, _) => None,
+                (true, Some(path)) => Some(path),
+                (true, None) => Some(get_default_ssh_config_path()?),
+            };
+            // Aliases of the sections of other regions and profiles aren't reused
+            let taken = match path {
+                Some(ref path) => get_other_aliases(path, &profile, &region)?,
+                None => HashSet::new(),
+            };
+            let block = render_block(
+                &instances,
+                &region,
+                &profile,
+                pinned_profile,
+                &prefix,
+                user,
+                &taken,
+            );
+            if let Some(path) = path {
+                update_ssh_config(&path, &block, &profile, &region)?;
+                eprintln!(
+                    "Wrote {} hosts to {}",
+                    instances.len(),
+                    path.to_string_lossy()
+                );
+            } else {
+                print_output(&block)?;
+            }
+        }
+        Command::Bookmark { action } => match action {
+            BookmarkCommand::Add { alias, target } => {
+                let alias = alias.trim_start_matches('@').to_string();
+                let is_instance_id =
+                    target.starts_with("i-") && !target.contains(char::is_whitespace);
+                let bookmark = Bookmark {
+                    region: require_region(region)?,
+                    profile: profile_override(),
+                    instance_id: is_instance_id.then(|| target.clone()),
+                    query: (!is_instance_id).then_some(target),
+                };
+                config.add_bookmark(&alias, &bookmark)?;
+            }
+            BookmarkCommand::List => {
+                let bookmarks = config.get_bookmarks();
+                let width = bookmarks
+                    .iter()
+                    .map(|(alias, _)| alias.chars().count())
+                    .max()
+                    .unwrap_or_default();
+                let output: String = bookmarks
+                    .iter()
+                    .map(|(alias, bookmark)| {
+                        format!(
+                            "@{:width$}  {}  {}  {}\n",
+                            alias,
+                            bookmark.region,
+                            bookmark.profile.as_deref().unwrap_or("-"),
+                            bookmark.get_query(),
+                            width = width
+                        )
+                    })
+                    .collect();
+                print_output(&output)?;
+            }
+            BookmarkCommand::Remove { alias } => {
+                let alias = alias.trim_start_matches('@');
+                if config.get_bookmark(alias).is_none() {
+                    return Err(Failure::NoBookmark(alias.to_string()).into());
+                }
+                config.remove_bookmark(alias)?;
+            }
+        },
+        Command::Config {
+            action: ConfigCommand::Show { origin },
+        } => print_output(&config.show(origin))?,
+        Command::Completions { shell } => print_completions(shell),
+        Command::Doctor => unreachable!("the doctor runs before the config is loaded"),
+        Command::CompleteInstances => {
+            let bookmarks: String = config
+                .get_bookmarks()
+                .iter()
+                .map(|(alias, _)| format!("@{}\n", alias))
+                .collect();
+            print_output(&bookmarks)?;
+            print_output(&instance_candidates(&current_profile(), region.as_deref()))?;
+        }
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+/// Load the config layers, with the `--profile` and `--region` flags on top
+fn load_config(cli: &Cli) -> Result<Config> {
+    let mut config = Config::new(cli.config.clone())?;
+    if let Some(ref profile) = cli.profile {
+        config.set_flag("aws", "profile", profile, "--profile")?;
+    }
+    if let Some(ref region) = cli.region {
+        config.set_flag("aws", "region", region, "--region")?;
+    }
+    Ok(config)
+}
+
+const AWS_CLI_ERROR: i32 = 255;
+
+/// Start the session, exiting with its exit code
+fn start_session(instance: InstanceInfo, kind: SessionKind, print_only: bool) -> Result<ExitCode> {
+    if print_only {
+        print_command(&instance, &kind);
+        Ok(ExitCode::SUCCESS)
+    } else {
+        let mut entry = HistoryEntry::new(&instance, current_profile(), kind.get_mode());
+        let started = Instant::now();
+        let status = connect(instance, kind)?;
+        // Sessions that failed to connect, e.g. to an instance without the SSM agent, aren't worth
+        // coming back to
+        if status.success() {
+            entry.duration = Some(started.elapsed().as_secs());
+            record_connection(entry);
+        }
+        // The AWS CLI exits with 255 on its own errors, e.g. when the plugin is missing, but so
+        // can the remote command, so the failure is only suggested
+        if status.code() == Some(AWS_CLI_ERROR) {
+            eprintln!(
+                "Exit code 255: if the session failed to start rather than the remote command, \
+                 `sm_connect doctor` checks the setup"
+            );
+        }
+        Ok(from_exit_status(status))
+    }
+}
+
+/// Add a connection to the history. Failing to is only worth a warning, the session already ran.
+fn record_connection(entry: HistoryEntry) {
+    if let Err(e) = record(&entry) {
+        eprintln!("Warning: could not save the connection history: {:#}", e);
+    }
+}
+
+/// Run the TUI to pick instances. Fails with `RuntimeError::UserExit` when the user quits.
+async fn pick_instances(
+    config: Config,
+    region: Option<String>,
+    search: Option<String>,
+    multi_select: bool,
+) -> Result<Vec<InstanceInfo>> {
     let mut terminal = setup_terminal().context("setup failed")?;
-    let mut app = App::new();
+    let mut app = App::new(config, region, search);
+    if multi_select {
+        app.enable_multi_select();
+    }
     let selected = app.run(&mut terminal).await;
 
     restore_terminal(&mut terminal).context("restore terminal failed")?;
-    match selected {
-        Err(e) => match e.downcast_ref() {
-            Some(app::RuntimeError::UserExit) => {}
-            _ => {
-                println!("{:?}", e);
+    selected
+}
+
+/// Find the instance matching the query. The user picks one in the TUI, filtered by the query,
+/// when there is no query or region, or when the query matches several instances.
+async fn get_target(
+    config: Config,
+    region: Option<String>,
+    query: Option<String>,
+) -> Result<InstanceInfo> {
+    get_targets(config, region, query, false)
+        .await?
+        .into_iter()
+        .next()
+        .ok_or_else(|| app::RuntimeError::UserExit.into())
+}
+
+/// Like `get_target`, letting the user mark several instances in the TUI with `multi_select`
+async fn get_targets(
+    config: Config,
+    region: Option<String>,
+    query: Option<String>,
+    multi_select: bool,
+) -> Result<Vec<InstanceInfo>> {
+    let (region, query) = match resolve_bookmark(&config, query.as_deref())? {
+        Some(bookmark) => {
+            if bookmark.profile.is_some() {
+                set_profile(bookmark.profile.clone());
             }
-        },
-        Ok(instance) => connect(instance),
-    }
-    Ok(())
-}
-
-fn connect(instance: InstanceInfo) {
-    // Run the AWS command
-    // If fails, run SSH
-    let mut child = Command::new("aws")
-        .args([
-            "--region",
-            instance.get_region().as_ref(),
-            "ssm",
-            "start-session",
-            "--target",
-            &instance.get_instance_id(),
-        ])
-        .spawn()
-        .unwrap_or_else(|_| {
-            println!("failed to run aws ssm start-session. Falling back to SSH");
-            Command::new("ssh")
-                .args([instance.get_public_ip()])
-                .spawn()
-                .expect("Failed even with SSH")
-        });
-
-    // Catch SIGINT signal and do nothing
-    // So that actually ctrl+c works on the aws ssm session
-    let mut _signals = Signals::new([SIGINT]).unwrap();
-
-    child.wait().expect("failed to aws sm connect");
+            (Some(bookmark.region.clone()), Some(bookmark.get_query()))
+        }
+        None => (region, query),
+    };
+    let (Some(region), Some(query)) = (region.clone(), query.clone()) else {
+        return pick_instances(config, region, query, multi_select).await;
+    };
+    let matches: Vec<InstanceInfo> = fetch_region(&config, &region)
+        .await?
+        .into_iter()
+        .filter(|instance| instance.matches_query(&query))
+        .collect();
+    match matches.len() {
+        0 => return Err(Failure::NoMatch(query).into()),
+        1 => return Ok(matches),
+        _ => {}
+    }
+    pick_instances(config, Some(region), Some(query), multi_select).await
+}
+
+/// The bookmark an `@alias` query refers to, none for other queries
+fn resolve_bookmark(config: &Config, query: Option<&str>) -> Result<Option<Bookmark>> {
+    let Some(alias) = query.and_then(|query| query.strip_prefix('@')) else {
+        return Ok(None);
+    };
+    match config.get_bookmark(alias) {
+        Some(bookmark) => Ok(Some(bookmark)),
+        None => Err(Failure::NoBookmark(alias.to_string()).into()),
+    }
+}
+
+fn require_region(region: Option<String>) -> Result<String> {
+    region.context("--region, or `region` in the [aws] config section, is required here")
+}
+
+/// Fetch the instances of a region, updating the cache so a TUI opened afterwards doesn't refetch
+async fn fetch_region(config: &Config, region: &str) -> Result<Vec<InstanceInfo>> {
+    let profile = current_profile();
+    let filters = config.get_instance_filters(region, &profile);
+    let mut instances = fetch_instances(Region::new(region.to_string()), &filters).await?;
+    instances.sort_by_key(|instance_info| instance_info.get_name().to_owned());
+    let _ = save_inventory(&profile, region, &filters, &instances);
+    Ok(instances)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bookmark_queries_resolve_to_their_bookmark() {
+        let path =
+            std::env::temp_dir().join(format!("sm_connect_main_{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[bookmarks.db]\nregion = \"eu-west-1\"\nprofile = \"prod\"\ninstance_id = \"i-0123\"\n\n\
+             [bookmarks.\"web.prod\"]\nregion = \"us-east-1\"\nquery = \"tag:Role=web\"\n",
+        )
+        .unwrap();
+        let config = Config::new(Some(path.clone()));
+        std::fs::remove_file(&path).unwrap();
+        let config = config.unwrap();
+
+        let db = resolve_bookmark(&config, Some("@db")).unwrap().unwrap();
+        assert_eq!(db.region, "eu-west-1");
+        assert_eq!(db.profile.as_deref(), Some("prod"));
+        assert_eq!(db.get_query(), "i-0123");
+        let web = resolve_bookmark(&config, Some("@web.prod"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(web.profile, None);
+        assert_eq!(web.get_query(), "tag:Role=web");
+
+        assert!(resolve_bookmark(&config, Some("db")).unwrap().is_none());
+        assert!(resolve_bookmark(&config, None).unwrap().is_none());
+        let missing = resolve_bookmark(&config, Some("@cache")).unwrap_err();
+        assert!(matches!(
+            missing.downcast_ref::<Failure>(),
+            Some(Failure::NoBookmark(alias)) if alias == "cache"
+        ));
+    }
 }
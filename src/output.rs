@@ -0,0 +1,182 @@
+use std::io::{self, Write};
+
+use anyhow::Result;
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+use crate::aws::{InstanceInfo, FIELDS};
+
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum OutputFormat {
+    Json,
+    Csv,
+    Table,
+}
+
+/// Check every field is known, so typos fail loudly instead of printing empty columns
+pub fn validate_fields(fields: &[String]) -> Result<()> {
+    for field in fields {
+        if !field.starts_with("tag:") && !FIELDS.contains(&field.as_str()) {
+            return Err(anyhow::anyhow!(
+                "Unknown field '{}', expected tag:<Key> or one of: {}",
+                field,
+                FIELDS.join(", ")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// `3m`, `5h` or `2d`, from seconds
+pub fn format_elapsed(seconds: u64) -> String {
+    match seconds {
+        s if s < 60 => format!("{}s", s),
+        s if s < 3600 => format!("{}m", s / 60),
+        s if s < 86400 => format!("{}h", s / 3600),
+        s => format!("{}d", s / 86400),
+    }
+}
+
+pub fn format_instances(
+    instances: &[InstanceInfo],
+    fields: &[String],
+    format: OutputFormat,
+) -> String {
+    let rows: Vec<Vec<String>> = instances
+        .iter()
+        .map(|instance| {
+            fields
+                .iter()
+                .map(|field| instance.get_field(field).unwrap_or_default())
+                .collect()
+        })
+        .collect();
+    match format {
+        OutputFormat::Json => format_json(fields, &rows),
+        OutputFormat::Csv => format_csv(fields, &rows),
+        OutputFormat::Table => format_table(fields, &rows),
+    }
+}
+
+fn format_json(fields: &[String], rows: &[Vec<String>]) -> String {
+    let objects: Vec<Value> = rows
+        .iter()
+        .map(|row| {
+            let object: Map<String, Value> = fields
+                .iter()
+                .cloned()
+                .zip(row.iter().map(|value| Value::String(value.clone())))
+                .collect();
+            Value::Object(object)
+        })
+        .collect();
+    format!(
+        "{}\n",
+        serde_json::to_string_pretty(&objects).unwrap_or_default()
+    )
+}
+
+fn format_csv(fields: &[String], rows: &[Vec<String>]) -> String {
+    std::iter::once(fields)
+        .chain(rows.iter().map(|row| row.as_slice()))
+        .map(|row| {
+            let line: Vec<String> = row.iter().map(|value| csv_escape(value)).collect();
+            format!("{}\n", line.join(","))
+        })
+        .collect()
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn format_table(fields: &[String], rows: &[Vec<String>]) -> String {
+    let header: Vec<String> = fields.iter().map(|field| field.to_uppercase()).collect();
+    let widths: Vec<usize> = (0..fields.len())
+        .map(|i| {
+            std::iter::once(&header)
+                .chain(rows.iter())
+                .map(|row| row[i].chars().count())
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    std::iter::once(&header)
+        .chain(rows.iter())
+        .map(|row| {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(widths.iter())
+                .map(|(value, width)| format!("{:width$}", value, width = width))
+                .collect();
+            format!("{}\n", cells.join("  ").trim_end())
+        })
+        .collect()
+}
+
+/// Split a template like `{name} {private_ip}` into literal text and field placeholders
+fn parse_template(template: &str) -> Result<Vec<(String, Option<String>)>> {
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            return Err(anyhow::anyhow!("Unclosed '{{' in template '{}'", template));
+        };
+        parts.push((
+            rest[..start].to_string(),
+            Some(rest[start + 1..start + end].to_string()),
+        ));
+        rest = &rest[start + end + 1..];
+    }
+    parts.push((rest.to_string(), None));
+    Ok(parts)
+}
+
+/// Check the template parses and only uses known fields, before the TUI is opened
+pub fn validate_template(template: &str) -> Result<()> {
+    let fields: Vec<String> = parse_template(template)?
+        .into_iter()
+        .filter_map(|(_, field)| field)
+        .collect();
+    validate_fields(&fields)
+}
+
+/// Replace every `{field}` in the template with the value of the instance
+pub fn render_template(template: &str, instance: &InstanceInfo) -> Result<String> {
+    Ok(parse_template(template)?
+        .into_iter()
+        .map(|(text, field)| {
+            let value = field
+                .and_then(|field| instance.get_field(&field))
+                .unwrap_or_default();
+            format!("{}{}", text, value)
+        })
+        .collect())
+}
+
+/// Print to stdout, treating a closed pipe (e.g. `| head`) as success
+pub fn print_output(output: &str) -> Result<()> {
+    match io::stdout().lock().write_all(output.as_bytes()) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => Ok(result?),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn json_keeps_the_order_of_the_fields() {
+        let fields = ["name".to_string(), "id".to_string()];
+        let rows = [vec!["web-1".to_string(), "i-0123".to_string()]];
+        assert_eq!(
+            format_json(&fields, &rows),
+            "[\n  {\n    \"name\": \"web-1\",\n    \"id\": \"i-0123\"\n  }\n]\n"
+        );
+    }
+}
@@ -0,0 +1,161 @@
+use std::process::{Command, ExitStatus};
+
+use anyhow::Result;
+
+use signal_hook::{consts::signal::*, iterator::Signals};
+
+use crate::aws::{profile_override, InstanceInfo};
+use crate::exit::Failure;
+
+/// The kind of Session Manager session to start on an instance
+#[derive(Debug, Clone)]
+pub enum SessionKind {
+    Shell,
+    PortForward {
+        local_port: u16,
+        remote_port: u16,
+        remote_host: Option<String>,
+    },
+    Exec {
+        command: String,
+    },
+}
+
+impl SessionKind {
+    /// How the session shows in the connection history
+    pub fn get_mode(&self) -> &'static str {
+        match self {
+            SessionKind::Shell => "shell",
+            SessionKind::PortForward { .. } => "forward",
+            SessionKind::Exec { .. } => "exec",
+        }
+    }
+}
+
+/// Build the `aws ssm start-session` invocation for a session
+pub fn build_command(instance: &InstanceInfo, kind: &SessionKind) -> Command {
+    let mut command = Command::new("aws");
+    if let Some(profile) = profile_override() {
+        command.args(["--profile", &profile]);
+    }
+    command.args([
+        "--region",
+        instance.get_region().as_ref(),
+        "ssm",
+        "start-session",
+        "--target",
+        &instance.get_instance_id(),
+    ]);
+    match kind {
+        SessionKind::Shell => {}
+        SessionKind::PortForward {
+            local_port,
+            remote_port,
+            remote_host,
+        } => {
+            let parameters = match remote_host {
+                Some(host) => format!(
+                    "host={},portNumber={},localPortNumber={}",
+                    host, remote_port, local_port
+                ),
+                None => format!("portNumber={},localPortNumber={}", remote_port, local_port),
+            };
+            let document = match remote_host {
+                Some(_) => "AWS-StartPortForwardingSessionToRemoteHost",
+                None => "AWS-StartPortForwardingSession",
+            };
+            command.args(["--document-name", document, "--parameters", &parameters]);
+        }
+        SessionKind::Exec { command: to_run } => {
+            let parameters = serde_json::json!({ "command": [to_run] }).to_string();
+            command.args([
+                "--document-name",
+                "AWS-StartInteractiveCommand",
+                "--parameters",
+                &parameters,
+            ]);
+        }
+    }
+    command
+}
+
+/// Environment variables that change what the AWS CLI does. Credentials are left out on purpose,
+/// printed commands end up in tickets and chats.
+const PRINTED_ENV_VARS: &[&str] = &[
+    "AWS_PROFILE",
+    "AWS_REGION",
+    "AWS_DEFAULT_REGION",
+    "AWS_CONFIG_FILE",
+    "AWS_SHARED_CREDENTIALS_FILE",
+    "AWS_CA_BUNDLE",
+    "AWS_ENDPOINT_URL",
+];
+
+/// Quote a word for POSIX shells, leaving it as is when that's safe
+pub fn shell_quote(word: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_-./:=@,+%".contains(c);
+    if !word.is_empty() && word.chars().all(is_safe) {
+        word.to_string()
+    } else {
+        format!("'{}'", word.replace('\'', "'\\''"))
+    }
+}
+
+/// Render a command as a copy-pasteable shell line, prefixed with the environment it runs with
+pub fn format_command(command: &Command) -> String {
+    let mut env: Vec<(String, String)> = PRINTED_ENV_VARS
+        .iter()
+        .filter_map(|name| Some((name.to_string(), std::env::var(name).ok()?)))
+        .collect();
+    for (name, value) in command.get_envs() {
+        let name = name.to_string_lossy().to_string();
+        env.retain(|(existing, _)| existing != &name);
+        if let Some(value) = value {
+            env.push((name, value.to_string_lossy().to_string()));
+        }
+    }
+    env.iter()
+        .map(|(name, value)| format!("{}={}", name, shell_quote(value)))
+        .chain(std::iter::once(shell_quote(
+            &command.get_program().to_string_lossy(),
+        )))
+        .chain(
+            command
+                .get_args()
+                .map(|arg| shell_quote(&arg.to_string_lossy())),
+        )
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Print the command that would start the session instead of running it
+pub fn print_command(instance: &InstanceInfo, kind: &SessionKind) {
+    println!("{}", format_command(&build_command(instance, kind)));
+}
+
+/// Run the session and return its exit status, so it can be passed through
+pub fn connect(instance: InstanceInfo, kind: SessionKind) -> Result<ExitStatus> {
+    // Run the AWS command
+    // If fails, run SSH
+    let mut child = match build_command(&instance, &kind).spawn() {
+        Ok(child) => child,
+        Err(_) if matches!(kind, SessionKind::Shell) => {
+            eprintln!("failed to run aws ssm start-session. Falling back to SSH");
+            Command::new("ssh")
+                .args([instance.get_public_ip()])
+                .spawn()
+                .map_err(|e| Failure::SessionStart(format!("ssh: {}", e)))?
+        }
+        Err(e) => {
+            return Err(Failure::SessionStart(format!("aws ssm start-session: {}", e)).into());
+        }
+    };
+
+    // Catch SIGINT signal and do nothing
+    // So that actually ctrl+c works on the aws ssm session
+    let mut _signals = Signals::new([SIGINT]).unwrap();
+
+    Ok(child
+        .wait()
+        .map_err(|e| Failure::SessionStart(e.to_string()))?)
+}
@@ -0,0 +1,161 @@
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::aws::InstanceInfo;
+use crate::dirs::get_state_dir;
+
+/// Order of the instances table, by a field as in `--fields`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SortOrder {
+    pub field: String,
+    pub descending: bool,
+}
+
+impl Default for SortOrder {
+    fn default() -> Self {
+        SortOrder {
+            field: "name".to_string(),
+            descending: false,
+        }
+    }
+}
+
+impl SortOrder {
+    /// IPs compare as numbers and launch times chronologically, other fields as text.
+    /// Ties are ordered by name, then id, so the order is stable across refreshes.
+    pub fn compare(&self, a: &InstanceInfo, b: &InstanceInfo) -> Ordering {
+        let ip = |instance: &InstanceInfo| {
+            instance
+                .get_field(&self.field)
+                .and_then(|ip| ip.parse::<IpAddr>().ok())
+        };
+        let ordering = match self.field.as_str() {
+            "private_ip" | "public_ip" => ip(a).cmp(&ip(b)),
+            "launch_time" => a.get_launch_secs().cmp(&b.get_launch_secs()),
+            // The youngest instance has the smallest age
+            "launch_age" => b.get_launch_secs().cmp(&a.get_launch_secs()),
+            field => a.get_field(field).cmp(&b.get_field(field)),
+        };
+        let ordering = if self.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        };
+        ordering
+            .then_with(|| a.get_name().cmp(&b.get_name()))
+            .then_with(|| a.get_instance_id().cmp(&b.get_instance_id()))
+    }
+}
+
+fn get_sort_path() -> Result<PathBuf> {
+    Ok(get_state_dir()?.join("sort.json"))
+}
+
+fn read_sort_orders(path: &Path) -> Result<BTreeMap<String, SortOrder>> {
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    Ok(serde_json::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+/// The order the instances of a region were last sorted in, by name when unknown or unreadable
+pub fn load_sort(region: &str) -> SortOrder {
+    get_sort_path()
+        .and_then(|path| read_sort_orders(&path))
+        .ok()
+        .and_then(|mut orders| orders.remove(region))
+        .unwrap_or_default()
+}
+
+/// Remember the order the instances of a region are sorted in
+pub fn save_sort(region: &str, sort: &SortOrder) -> Result<()> {
+    write_sort(&get_sort_path()?, region, sort)
+}
+
+fn write_sort(path: &Path, region: &str, sort: &SortOrder) -> Result<()> {
+    // A corrupt file is replaced, the orders of other regions are only a convenience
+    let mut orders = read_sort_orders(path).unwrap_or_default();
+    orders.insert(region.to_string(), sort.clone());
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, serde_json::to_string_pretty(&orders)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_config::Region;
+    use aws_sdk_ec2::primitives::DateTime;
+    use aws_sdk_ec2::types::{Instance, Tag};
+
+    fn instance(id: &str, name: &str, private_ip: &str, launched_at: i64) -> InstanceInfo {
+        let instance = Instance::builder()
+            .instance_id(id)
+            .tags(Tag::builder().key("Name").value(name).build())
+            .private_ip_address(private_ip)
+            .launch_time(DateTime::from_secs(launched_at))
+            .build();
+        InstanceInfo::new(Region::new("eu-west-1"), instance)
+    }
+
+    fn sorted(field: &str, descending: bool, instances: &[InstanceInfo]) -> Vec<String> {
+        let sort = SortOrder {
+            field: field.to_string(),
+            descending,
+        };
+        let mut instances = instances.to_vec();
+        instances.sort_by(|a, b| sort.compare(a, b));
+        instances.iter().map(|i| i.get_instance_id()).collect()
+    }
+
+    #[test]
+    fn fields_compare_by_their_kind_and_ties_by_name_then_id() {
+        let instances = [
+            instance("i-3", "web", "10.0.0.10", 300),
+            instance("i-2", "web", "10.0.0.9", 100),
+            instance("i-1", "api", "10.0.0.9", 200),
+        ];
+        assert_eq!(
+            sorted("private_ip", false, &instances),
+            ["i-1", "i-2", "i-3"]
+        );
+        assert_eq!(
+            sorted("private_ip", true, &instances),
+            ["i-3", "i-1", "i-2"]
+        );
+        assert_eq!(
+            sorted("launch_time", false, &instances),
+            ["i-2", "i-1", "i-3"]
+        );
+        assert_eq!(
+            sorted("launch_age", false, &instances),
+            ["i-3", "i-1", "i-2"]
+        );
+        assert_eq!(sorted("name", true, &instances), ["i-2", "i-3", "i-1"]);
+    }
+
+    #[test]
+    fn sort_orders_are_kept_by_region() {
+        let path =
+            std::env::temp_dir().join(format!("sm_connect_sort_{}.json", std::process::id()));
+        std::fs::write(&path, "not json").unwrap();
+        let by_ip = SortOrder {
+            field: "private_ip".to_string(),
+            descending: true,
+        };
+        write_sort(&path, "eu-west-1", &by_ip).unwrap();
+        write_sort(&path, "us-east-1", &SortOrder::default()).unwrap();
+        let orders = read_sort_orders(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(orders.len(), 2);
+        assert_eq!(orders["eu-west-1"], by_ip);
+        assert_eq!(orders["us-east-1"], SortOrder::default());
+    }
+}
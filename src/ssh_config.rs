@@ -0,0 +1,202 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use home::home_dir;
+
+use crate::aws::InstanceInfo;
+use crate::dirs::write_atomically;
+use crate::session::shell_quote;
+
+pub fn get_default_ssh_config_path() -> Result<PathBuf> {
+    let Some(home_dir) = home_dir() else {
+        return Result::Err(anyhow::anyhow!("Could not find home directory"));
+    };
+    Ok(home_dir.join(".ssh").join("config"))
+}
+
+/// Host aliases must be a single word, anything else in the name becomes a dash
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "-_.".contains(c) {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect()
+}
+
+/// Pair each instance with its Host alias. Instances sharing a name get their id appended,
+/// so aliases don't change depending on which instances are running. Aliases already `taken` by
+/// the sections of other regions or profiles get the region appended, as ssh only uses the first.
+fn host_aliases<'a>(
+    instances: &'a [InstanceInfo],
+    prefix: &str,
+    region: &str,
+    taken: &HashSet<String>,
+) -> Vec<(String, &'a InstanceInfo)> {
+    let names: Vec<String> = instances
+        .iter()
+        .map(|instance| match sanitize(&instance.get_name()) {
+            name if name.is_empty() => instance.get_instance_id(),
+            name => name,
+        })
+        .collect();
+    let mut counts: HashMap<&String, usize> = HashMap::new();
+    for name in &names {
+        *counts.entry(name).or_default() += 1;
+    }
+    names
+        .iter()
+        .zip(instances)
+        .map(|(name, instance)| {
+            let alias = if counts[name] > 1 && name != &instance.get_instance_id() {
+                format!("{}{}-{}", prefix, name, instance.get_instance_id())
+            } else {
+                format!("{}{}", prefix, name)
+            };
+            if taken.contains(&alias) {
+                (format!("{}-{}", alias, region), instance)
+            } else {
+                (alias, instance)
+            }
+        })
+        .collect()
+}
+
+fn get_markers(profile: &str, region: &str) -> (String, String) {
+    (
+        format!("# BEGIN sm_connect {}/{}", profile, region),
+        format!("# END sm_connect {}/{}", profile, region),
+    )
+}
+
+/// Host aliases of the managed blocks of other profiles and regions in the ssh config
+pub fn get_other_aliases(path: &Path, profile: &str, region: &str) -> Result<HashSet<String>> {
+    if !path.exists() {
+        return Ok(HashSet::new());
+    }
+    let (begin, _) = get_markers(profile, region);
+    let mut aliases = HashSet::new();
+    let mut in_other_block = false;
+    for line in std::fs::read_to_string(path)?.lines() {
+        let line = line.trim();
+        if line.starts_with("# BEGIN sm_connect ") {
+            in_other_block = line != begin;
+        } else if line.starts_with("# END sm_connect ") {
+            in_other_block = false;
+        } else if let Some(alias) = line.strip_prefix("Host ").filter(|_| in_other_block) {
+            aliases.insert(alias.trim().to_string());
+        }
+    }
+    Ok(aliases)
+}
+
+/// Render the managed block with one Host entry per instance, connecting through Session Manager
+pub fn render_block(
+    instances: &[InstanceInfo],
+    region: &str,
+    profile: &str,
+    profile_override: Option<String>,
+    prefix: &str,
+    user: Option<String>,
+    taken: &HashSet<String>,
+) -> String {
+    let mut proxy_command = vec!["aws".to_string()];
+    if let Some(profile) = profile_override {
+        proxy_command.extend(["--profile".to_string(), shell_quote(&profile)]);
+    }
+    proxy_command.extend(
+        [
+            "--region",
+            &shell_quote(region),
+            "ssm",
+            "start-session",
+            "--target",
+            "%h",
+            "--document-name",
+            "AWS-StartSSHSession",
+            "--parameters",
+            "portNumber=%p",
+        ]
+        .map(|s| s.to_string()),
+    );
+
+    let (begin, end) = get_markers(profile, region);
+    let mut block = format!(
+        "{}\n# Generated by `sm_connect ssh-config`, changes inside this block are overwritten\n",
+        begin
+    );
+    for (alias, instance) in host_aliases(instances, prefix, region, taken) {
+        block.push_str(&format!("Host {}\n", alias));
+        block.push_str(&format!("    HostName {}\n", instance.get_instance_id()));
+        if let Some(ref user) = user {
+            block.push_str(&format!("    User {}\n", user));
+        }
+        block.push_str(&format!("    ProxyCommand {}\n", proxy_command.join(" ")));
+    }
+    block.push_str(&end);
+    block.push('\n');
+    block
+}
+
+/// Replace the managed block for the profile and region in the ssh config, or append it
+pub fn update_ssh_config(path: &Path, block: &str, profile: &str, region: &str) -> Result<()> {
+    // A symlinked ssh config, e.g. one kept in a dotfiles repository, is updated where it points
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let existing = if path.exists() {
+        std::fs::read_to_string(&path)?
+    } else {
+        String::default()
+    };
+    let (begin, end) = get_markers(profile, region);
+
+    let updated = match (existing.find(&begin), existing.find(&end)) {
+        (Some(start), Some(stop)) if start < stop => {
+            let after = &existing[stop + end.len()..];
+            format!(
+                "{}{}{}",
+                &existing[..start],
+                block,
+                after.strip_prefix('\n').unwrap_or(after)
+            )
+        }
+        _ if existing.is_empty() => block.to_string(),
+        _ if existing.ends_with('\n') => format!("{}\n{}", existing, block),
+        _ => format!("{}\n\n{}", existing, block),
+    };
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    // Written next to the file and renamed, so a failure never leaves a truncated ssh config
+    write_atomically(&path, &updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn other_aliases_come_from_the_other_managed_blocks() {
+        let path = std::env::temp_dir().join(format!("sm_connect_ssh_{}", std::process::id()));
+        let blocks = [
+            render_block(&[], "eu-west-1", "prod", None, "", None, &HashSet::new()),
+            render_block(&[], "us-east-1", "prod", None, "", None, &HashSet::new()),
+        ];
+        let content = format!(
+            "Host bastion\n    HostName 10.0.0.1\n\n{}",
+            blocks
+                .iter()
+                .zip(["Host web\n", "Host db\n"])
+                .map(|(block, host)| block.replacen('\n', &format!("\n{}", host), 1))
+                .collect::<String>()
+        );
+        std::fs::write(&path, content).unwrap();
+        let aliases = get_other_aliases(&path, "prod", "eu-west-1").unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(aliases, HashSet::from(["db".to_string()]));
+    }
+}
@@ -0,0 +1,131 @@
+use std::process::Command;
+
+use anyhow::Result;
+use clap::ValueEnum;
+
+use crate::aws::InstanceInfo;
+use crate::exit::Failure;
+use crate::session::{build_command, format_command, SessionKind};
+
+/// Where the sessions of several instances are opened
+#[derive(ValueEnum, Debug, Clone, Copy)]
+pub enum TmuxLayout {
+    /// Tiled panes in a new window
+    Panes,
+    /// One window per instance
+    Windows,
+}
+
+pub fn in_tmux() -> bool {
+    std::env::var_os("TMUX").is_some_and(|value| !value.is_empty())
+}
+
+/// Pane and window title, the id when the instance has no name
+fn get_title(instance: &InstanceInfo) -> String {
+    match instance.get_name() {
+        name if name.is_empty() => instance.get_instance_id(),
+        name => name,
+    }
+}
+
+/// Run tmux, returning what it printed
+fn tmux(args: &[&str]) -> Result<String> {
+    let output = Command::new("tmux")
+        .args(args)
+        .output()
+        .map_err(|e| Failure::SessionStart(format!("tmux: {}", e)))?;
+    if !output.status.success() {
+        return Err(Failure::SessionStart(format!(
+            "tmux {}: {}",
+            args.first().unwrap_or(&""),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ))
+        .into());
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Open a shell session per instance, in tiled panes of a new window or in a window each.
+/// Panes are titled with the instance names, and their input can be synchronized.
+pub fn open_in_tmux(
+    instances: &[InstanceInfo],
+    layout: TmuxLayout,
+    synchronize_panes: bool,
+) -> Result<()> {
+    // The session commands go through the tmux server, whose environment may be older than ours,
+    // so they carry the AWS environment variables like printed commands do
+    let commands: Vec<String> = instances
+        .iter()
+        .map(|instance| format_command(&build_command(instance, &SessionKind::Shell)))
+        .collect();
+
+    match layout {
+        TmuxLayout::Windows => {
+            for (instance, command) in instances.iter().zip(&commands) {
+                let pane = tmux(&[
+                    "new-window",
+                    "-P",
+                    "-F",
+                    "#{pane_id}",
+                    "-n",
+                    &get_title(instance),
+                    command,
+                ])?;
+                tmux(&["select-pane", "-t", &pane, "-T", &get_title(instance)])?;
+            }
+        }
+        TmuxLayout::Panes => {
+            let mut window = String::default();
+            for (instance, command) in instances.iter().zip(&commands) {
+                let pane = if window.is_empty() {
+                    let ids = tmux(&[
+                        "new-window",
+                        "-P",
+                        "-F",
+                        "#{window_id} #{pane_id}",
+                        "-n",
+                        "sm_connect",
+                        command,
+                    ])?;
+                    let (window_id, pane_id) = ids.split_once(' ').unwrap_or((&ids, &ids));
+                    window = window_id.to_string();
+                    pane_id.to_string()
+                } else {
+                    let pane = tmux(&[
+                        "split-window",
+                        "-t",
+                        &window,
+                        "-P",
+                        "-F",
+                        "#{pane_id}",
+                        command,
+                    ])?;
+                    // Re-tile after every split, otherwise tmux runs out of room for new panes
+                    tmux(&["select-layout", "-t", &window, "tiled"])?;
+                    pane
+                };
+                tmux(&["select-pane", "-t", &pane, "-T", &get_title(instance)])?;
+            }
+            tmux(&[
+                "set-option",
+                "-w",
+                "-t",
+                &window,
+                "pane-border-status",
+                "top",
+            ])?;
+            tmux(&[
+                "set-option",
+                "-w",
+                "-t",
+                &window,
+                "pane-border-format",
+                "#{pane_title}",
+            ])?;
+            if synchronize_panes {
+                tmux(&["set-option", "-w", "-t", &window, "synchronize-panes", "on"])?;
+            }
+        }
+    }
+    Ok(())
+}
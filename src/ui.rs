@@ -5,21 +5,35 @@ use crossterm::{
 use ratatui::prelude::*;
 
 use anyhow::{Context, Result};
-use std::io::{self, Stdout};
+use std::fs::OpenOptions;
+use std::io::{self, IsTerminal, Write};
+
+pub type Tui = Terminal<CrosstermBackend<Box<dyn Write>>>;
 
 /// Setup the terminal. This is where you would enable raw mode, enter the alternate screen, and
 /// hide the cursor. This example does not handle errors. A more robust application would probably
 /// want to handle errors and ensure that the terminal is restored to a sane state before exiting.
-pub fn setup_terminal() -> Result<Terminal<CrosstermBackend<Stdout>>> {
-    let mut stdout = io::stdout();
+/// When stdout is redirected, e.g. `$(sm_connect pick)`, the UI is drawn on /dev/tty instead so
+/// stdout only gets the output.
+pub fn setup_terminal() -> Result<Tui> {
+    let mut output: Box<dyn Write> = if io::stdout().is_terminal() {
+        Box::new(io::stdout())
+    } else {
+        Box::new(
+            OpenOptions::new()
+                .write(true)
+                .open("/dev/tty")
+                .context("unable to open /dev/tty")?,
+        )
+    };
     enable_raw_mode().context("failed to enable raw mode")?;
-    execute!(stdout, EnterAlternateScreen).context("unable to enter alternate screen")?;
-    Terminal::new(CrosstermBackend::new(stdout)).context("creating terminal failed")
+    execute!(output, EnterAlternateScreen).context("unable to enter alternate screen")?;
+    Terminal::new(CrosstermBackend::new(output)).context("creating terminal failed")
 }
 
 /// Restore the terminal. This is where you disable raw mode, leave the alternate screen, and show
 /// the cursor.
-pub fn restore_terminal(terminal: &mut Terminal<CrosstermBackend<Stdout>>) -> Result<()> {
+pub fn restore_terminal(terminal: &mut Tui) -> Result<()> {
     disable_raw_mode().context("failed to disable raw mode")?;
     execute!(terminal.backend_mut(), LeaveAlternateScreen)
         .context("unable to switch to main screen")?;